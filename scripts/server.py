@@ -1,48 +1,61 @@
-from http.server import BaseHTTPRequestHandler, HTTPServer
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+import threading
 import time
 from collections import deque
 
 hostName = "localhost"
 serverPort = 8000
 
+# How long each response takes, so that requests which are sent one at a time are slow
+response_delay = 0.05
+
 time_diffs = deque([], maxlen=100)
 
-last = 0
-bucket = 0
+# Each path is its own bucket, as [remaining, time of the last reset]
+buckets = {}
 max_bucket = 5000
+reset_after = 3
+lock = threading.Lock()
 
 class MyServer(BaseHTTPRequestHandler):
 
     def do_GET(self):
-        global last
-        global bucket
-
-        if time.time() - last > 3:
-            bucket = max_bucket
-        elif bucket < 0:
-            print("ERROR: BUCKET EMPTY!!!")
-        last = time.time()
+        with lock:
+            remaining, last = buckets.get(self.path, (max_bucket, 0))
+            if time.time() - last > reset_after:
+                remaining = max_bucket
+                last = time.time()
+            elif remaining <= 0:
+                print("ERROR: BUCKET EMPTY!!!")
+            remaining -= 1
+            buckets[self.path] = (remaining, last)
 
-        bucket -= 1
+        time.sleep(response_delay)
 
         self.send_response(200)
         t = time.strftime("%a, %d %b %Y %H:%M:%S GMT", time.gmtime())
         self.send_header("Content-type", "text/html")
-        self.send_header("X-RateLimit-Remaining", str(bucket))
+        self.send_header("X-RateLimit-Bucket", self.path)
+        self.send_header("X-RateLimit-Remaining", str(remaining))
         self.send_header("X-RateLimit-Limit", str(max_bucket))
 
-        self.send_header("X-RateLimit-Reset", str(int(time.time() + 3)))
+        self.send_header("X-RateLimit-Reset", str(int(last + reset_after)))
+        self.send_header("X-RateLimit-Reset-After", "%.3f" % max(last + reset_after - time.time(), 0))
         self.send_header("Date", t)
         self.end_headers()
         self.wfile.write(bytes("Hi!", "utf-8"))
-        
-        time_diffs.append(time.time())
 
-        print(time.time(), len(time_diffs) / (time_diffs[-1] - time_diffs[0]))
+        with lock:
+            time_diffs.append(time.time())
+            if len(time_diffs) > 1:
+                print(time.time(), len(time_diffs) / (time_diffs[-1] - time_diffs[0]))
+
+    def log_message(self, format, *args):
+        pass
 
 if __name__ == "__main__":        
 
-    webServer = HTTPServer((hostName, serverPort), MyServer)
+    webServer = ThreadingHTTPServer((hostName, serverPort), MyServer)
     print("Server started http://%s:%s" % (hostName, serverPort))
 
     try:
@@ -51,4 +64,4 @@ if __name__ == "__main__":
         pass
 
     webServer.server_close()
-    print("Server stopped.")
\ No newline at end of file
+    print("Server stopped.")
@@ -0,0 +1,74 @@
+//! Measures how many requests per second the http client can send when they are spread over several buckets.
+//! It runs against the rate limit stand-in in `scripts/server.py`, which takes a while to respond to each request.
+//!
+//! Start the server with `python3 scripts/server.py`, and then run `cargo bench --bench http_throughput`
+
+use std::time::Instant;
+
+use discrab::{
+    core::{
+        http::{
+            rate_limit_client::{send_request_noparse, RLClient, RequestRoute},
+            request_queue::BasicHttpQueue,
+        },
+        interactions::handler::gateway_handle::GatewayHandle,
+        settings::Settings,
+    },
+    Context, ShutdownHandle,
+};
+use futures_util::future::join_all;
+use hyper::{Body, Method, Request};
+
+const SERVER_URL: &str = "http://localhost:8000";
+/// The number of buckets that the requests are spread over
+const BUCKETS: usize = 10;
+/// The number of requests that are sent in each bucket
+const REQUESTS_PER_BUCKET: usize = 20;
+
+fn main() {
+    let client = RLClient::new(BasicHttpQueue::new(60));
+    let ctx = Context {
+        token: "benchmark".to_string(),
+        request_stream: client.get_req_sender(),
+        settings: Settings::default(),
+        shard_id: 0,
+        gateway: GatewayHandle::default(),
+        shutdown: ShutdownHandle::default(),
+        cache: (),
+    };
+
+    let requests = (0..BUCKETS)
+        .flat_map(|bucket| (0..REQUESTS_PER_BUCKET).map(move |_| bucket))
+        .map(|bucket| {
+            let path = format!("/bucket/{}", bucket);
+            let request = Request::builder()
+                .method(Method::GET)
+                .uri(format!("{}{}", SERVER_URL, path))
+                .body(Body::empty())
+                .unwrap();
+            let route = RequestRoute {
+                base_route: path,
+                major_param: String::new(),
+            };
+            send_request_noparse(ctx.clone(), route, request)
+        });
+
+    let start = Instant::now();
+    let results = async_std::task::block_on(join_all(requests));
+    let elapsed = start.elapsed();
+
+    let failed = results.iter().filter(|result| result.is_err()).count();
+    if failed > 0 {
+        eprintln!(
+            "{} requests failed. Is the server in scripts/server.py running?",
+            failed
+        );
+    }
+    println!(
+        "Sent {} requests over {} buckets in {:.2?} ({:.1} requests/s)",
+        results.len(),
+        BUCKETS,
+        elapsed,
+        results.len() as f64 / elapsed.as_secs_f64()
+    );
+}
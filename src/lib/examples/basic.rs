@@ -22,7 +22,11 @@ async fn main() {
 
     let mut bot = Bot::new(token);
     bot.settings().set_debug(true);
+    // Close the gateway connections and send the queued requests before exiting on SIGTERM or Ctrl+C
+    bot.shutdown_handle().shutdown_on_signal();
     let test = TestCmd::new();
     register_all!(bot, test, EchoCmd, PingSlashCmd {a: 0}, MsgEvent);
-    bot.listen().await;
+    if let Err(error) = bot.listen().await {
+        eprintln!("{}", error);
+    }
 }
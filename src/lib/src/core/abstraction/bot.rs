@@ -1,22 +1,43 @@
-use std::sync::Arc;
+use std::{
+    collections::HashSet,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
 
-use serde_json::json;
+use crossbeam_channel::{select, Receiver};
+use serde_json::Value;
 
 use crate::{
     core::{
-        http::{rate_limit_client::RLClient, request_queue::BasicHttpQueue},
-        interactions::handler::{websocket::WebsocketEventHandler, SocketClient},
+        http::{
+            rate_limit_client::RLClient,
+            request_queue::{BasicHttpQueue, HttpQueue},
+            retry_policy::RetryPolicy,
+        },
+        interactions::handler::{
+            events::{lifecycle_payloads::AllShardsReady, IdentifyPayloadData, Intents},
+            gateway_handle::GatewayHandle,
+            shard_manager::ShardManager,
+            SocketClient,
+        },
         settings::Settings,
     },
     discord::resources::user::User,
-    util::logger::print_debug,
-    Registerable,
+    util::{
+        error::Error,
+        logger::{print_debug, print_warning},
+    },
+    Events, Registerable,
 };
 
 use super::{
-    context::Context, event_dispatcher::EventDispatcher, interaction_router::InteractionRouter, traits::{RegisterableType, RegFns},
+    context::Context, event_dispatcher::EventDispatcher, guild_tracker::GuildTracker, interaction_router::InteractionRouter, shutdown::ShutdownHandle, traits::{RegisterableType, RegFns},
 };
 
+/// How long the bot waits for connections to close and requests to be sent when it shuts down, if it isn't set in the settings
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// The main bot abstraction
 ///
 /// ```rust,no_run
@@ -25,7 +46,7 @@ use super::{
 ///  // Register Commands/Events that you want to listen for
 ///  register_commands!(bot, command, ...);
 ///  // Have the bot listen for events and commands, and await the bot to finish listening (Shouldn't escape unless there is an error)
-///  bot.listen().await;
+///  bot.listen().await.unwrap();
 /// ```
 pub struct Bot {
     /// Global context for the bot
@@ -36,16 +57,44 @@ pub struct Bot {
     token: String,
     /// Interaction router that distributes interactions to the respective handlers. Is registered with the event dispatcher
     interaction_router: Arc<InteractionRouter>,
+    /// The events that handlers have been registered for. Used to work out which intents the bot needs
+    registered_events: Vec<Events>,
+    /// The http client that the bot's requests are sent through
+    client: RLClient,
 }
 
 impl Bot {
     /// Create a new bot instance with a token. Your bot's token can be found in the discord developer portal
     pub fn new(token: String) -> Self {
-        let client = RLClient::new(BasicHttpQueue::new(60));
+        Bot::with_client(token, RLClient::new(BasicHttpQueue::new(60)))
+    }
+
+    /**
+     * Create a new bot instance that sends its requests in the order decided by the given queue
+     * @param token The bot's token
+     * @param queue Decides the order that requests are sent in, like `BasicHttpQueue` or `PriorityHttpQueue`
+     */
+    pub fn with_queue<T>(token: String, queue: T) -> Self
+    where
+        T: HttpQueue + Send + 'static,
+    {
+        Bot::with_client(token, RLClient::new(queue))
+    }
+
+    /**
+     * Create a new bot instance that sends its requests through the given http client.
+     * This can be used to change the request queue, or to send requests through a different transport, like a proxy or a `MockTransport`
+     * @param token The bot's token
+     * @param client The http client that the bot's requests are sent through
+     */
+    pub fn with_client(token: String, client: RLClient) -> Self {
         let ctx = Context {
             token: token.clone(),
             request_stream: client.get_req_sender(),
             settings: Settings::default(),
+            shard_id: 0,
+            gateway: GatewayHandle::default(),
+            shutdown: ShutdownHandle::default(),
             cache: (),
         };
         let event_dispatcher = EventDispatcher::new();
@@ -56,6 +105,8 @@ impl Bot {
             ctx,
             event_dispatcher,
             token,
+            registered_events: Vec::new(),
+            client,
         }
     }
 
@@ -64,11 +115,24 @@ impl Bot {
         &mut self.ctx.settings
     }
 
+    /// Set the retry policy for requests that fail. It can be changed for a single request with `ctx.with_retry_policy`
+    pub fn set_retry_policy(&self, retry_policy: RetryPolicy) {
+        self.client.set_retry_policy(retry_policy);
+    }
+
+    /// Get a handle that can be used to stop the bot. It is also available to handlers through the context
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.ctx.shutdown.clone()
+    }
+
     /// You can use this to register a command handler, or an interaction handler. The Registerable Trait is implemented for you through the `#[event_handler]` or `#[command]` macro/
     pub async fn register(mut self, to_register: Arc<impl Registerable + RegFns>) -> Self {
         let registerable_type= to_register.get_reg_type();
             match registerable_type {
                 RegisterableType::Event => {
+                    if let Some(event) = to_register.get_event_type() {
+                        self.registered_events.push(event);
+                    }
                     to_register.reg_event(&mut self.event_dispatcher);
                 }
                 RegisterableType::Command => {
@@ -80,44 +144,176 @@ impl Bot {
     }
 
     /// Listen for events and commands. This will block the thread until the bot is closed (when awaited).
-    pub async fn listen(&mut self) {
-        let event_handler = WebsocketEventHandler::create(self.ctx.clone()).await;
-
+    /// A connection is opened for every shard in the shard settings, and if one is lost, it will be resumed automatically.
+    /// If the gateway closes a connection in a way that can't be recovered from, such as an invalid token or disallowed intents,
+    /// the bot is shut down and the error is returned. An error is also returned before connecting if the shard config selects shards that don't exist.
+    /// Once shutdown is requested through the shutdown handle, the gateway connections are closed, no more events are handled,
+    /// and this returns once the queued requests have been sent, or the shutdown timeout has passed.
+    pub async fn listen(&mut self) -> Result<(), Error> {
         // Register the interaction router
         self.event_dispatcher
             .InteractionCtx
             .subscribe(self.interaction_router.clone());
 
-        if self.ctx.settings.debug {
-            print_debug("BOT", "Identifying Self".to_string());
-        }
-
         // Identify object for the bot
-        let cmd = json!({
-            "op": 2,
-            "d": {
-                "token": self.token,
-                "properties": {
-                    "$os": "linux",
-                    "$browser": "discord.rs",
-                    "$device": "discord.rs",
-                },
-                "intents": 1 << 9,
-            }
-        });
+        let settings = &self.ctx.settings;
+        let identify = IdentifyPayloadData {
+            token: self.token.clone(),
+            properties: Some(settings.identify_properties.clone()),
+            compress: None,
+            large_threshold: settings.large_threshold,
+            shard: None,
+            presence: settings.presence.clone(),
+            intents: settings.intents.unwrap_or_else(|| self.required_intents()),
+        };
+        self.check_intents(identify.intents);
 
-        // Send the identify object to the websocket
-        event_handler.send_command(cmd.to_string());
+        // Each shard will identify with this object, and then resume the session whenever it reconnects
+        let shard_manager = ShardManager::create(self.ctx.clone(), identify).await?;
 
         // Listen for events
-        let cmds = event_handler.get_command_channel();
+        let cmds = shard_manager.get_command_channel();
         if self.ctx.settings.debug {
             print_debug("BOT", "Listening...".to_string());
         }
         
-        while let Ok((command, data)) = cmds.recv() {
-            self.event_dispatcher
-                .route_event(self.ctx.clone(), command, data);
+        let fatal_errors = self.ctx.gateway.fatal_errors();
+        let shutdown = self.ctx.shutdown.signal();
+        // The shards that have become ready, so that All Shards Ready can be sent once every shard is ready
+        let shard_count = shard_manager.shards().len();
+        let mut ready_shards = HashSet::new();
+        // Used to split guild creates and deletes into joins, leaves and availability changes
+        let mut guild_tracker = GuildTracker::default();
+        loop {
+            select! {
+                recv(cmds) -> event => {
+                    let (shard_id, command, data) = match event {
+                        Ok(event) => event,
+                        Err(_) => return Ok(()),
+                    };
+                    // Handlers run on this thread, so the shutdown signal is picked up once the current handler has finished
+                    if self.ctx.shutdown.is_requested() {
+                        continue;
+                    }
+                    let ctx = Context {
+                        shard_id,
+                        ..self.ctx.clone()
+                    };
+                    let shard_ready = command == "SHARD_READY";
+                    let derived = guild_tracker
+                        .track(&command, &data)
+                        .map(|event| (event, data.clone()));
+                    self.event_dispatcher.route_event(ctx.clone(), command, data);
+
+                    if let Some((event, data)) = derived {
+                        self.event_dispatcher
+                            .route_event(ctx.clone(), event.to_string(), data);
+                    }
+
+                    // Only sent the first time, not when a shard identifies again later
+                    if shard_ready && ready_shards.insert(shard_id) && ready_shards.len() == shard_count {
+                        let data = serde_json::to_value(AllShardsReady {
+                            shard_count: shard_count as u64,
+                        })
+                        .unwrap();
+                        self.event_dispatcher
+                            .route_event(ctx, "ALL_SHARDS_READY".to_string(), data);
+                    }
+                }
+                recv(fatal_errors) -> error => {
+                    if let Ok(error) = error {
+                        // The other shards and the queued requests are shut down, instead of being left running without the bot
+                        self.ctx.shutdown.shutdown();
+                        self.drain(cmds);
+                        return Err(error);
+                    }
+                }
+                recv(shutdown) -> _ => break,
+            }
+        }
+
+        self.drain(cmds);
+        Ok(())
+    }
+
+    /// Waits for every shard to close its connection, and for the queued requests to be sent, until the shutdown timeout has passed
+    fn drain(&self, events: Receiver<(u64, String, Value)>) {
+        if self.ctx.settings.debug {
+            print_debug("BOT", "Shutting down...".to_string());
+        }
+        let deadline = Instant::now()
+            + self
+                .ctx
+                .settings
+                .shutdown_timeout
+                .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT);
+
+        // The channel disconnects once every shard has closed its connection. Events that arrive in the meantime aren't handled
+        while events.recv_deadline(deadline).is_ok() {}
+
+        while self.client.pending_requests() > 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        if self.ctx.settings.debug {
+            print_debug(
+                "BOT",
+                format!(
+                    "Shut down with {} requests still pending",
+                    self.client.pending_requests()
+                ),
+            );
+        }
+    }
+
+    /// Gets the smallest set of intents that will deliver every event that a handler has been registered for.
+    /// Commands are received through interactions, which don't need any intents.
+    pub fn required_intents(&self) -> Intents {
+        self.registered_events
+            .iter()
+            .fold(Intents::empty(), |intents, event| intents | event.intents())
+    }
+
+    /// Warns about handlers that will never be called with the given intents, and about privileged intents that have to be enabled in the developer portal
+    fn check_intents(&self, intents: Intents) {
+        if self.ctx.settings.debug {
+            print_debug("BOT", format!("Identifying with intents {:?}", intents));
+        }
+
+        for event in &self.registered_events {
+            if !event.intents().is_empty() && !intents.intersects(event.intents()) {
+                print_warning(
+                    "INTENTS",
+                    format!(
+                        "A handler is registered for {:?}, but it will never be called without one of the intents {:?}",
+                        event,
+                        event.intents()
+                    ),
+                );
+            }
+        }
+
+        if self
+            .registered_events
+            .iter()
+            .any(|e| *e == Events::MessageCreate || *e == Events::MessageUpdate)
+            && !intents.contains(Intents::MESSAGE_CONTENT)
+        {
+            print_warning(
+                "INTENTS",
+                "A message handler is registered without the privileged MESSAGE_CONTENT intent. Messages that don't mention the bot will have empty content, embeds, attachments and components".to_string(),
+            );
+        }
+
+        let privileged = intents & Intents::privileged();
+        if !privileged.is_empty() {
+            print_warning(
+                "INTENTS",
+                format!(
+                    "Using privileged intents {:?}. They have to be enabled for the bot in the developer portal",
+                    privileged
+                ),
+            );
         }
     }
 
@@ -1,6 +1,25 @@
-use crossbeam_channel::Sender;
+use std::time::Duration;
 
-use crate::core::{http::rate_limit_client::RequestObject, settings::Settings};
+use crate::{
+    core::{
+        abstraction::shutdown::ShutdownHandle,
+        http::{
+            http_handle::HttpHandle, rate_limit_client::RequestSender,
+            request_queue::RequestPriority,
+            retry_policy::RetryPolicy,
+        },
+        interactions::handler::gateway_handle::{GatewayHandle, GuildMembers, MemberFilter},
+        settings::Settings,
+    },
+    discord::{
+        gateway::{
+            activity::Activity,
+            presence::{StatusType, UpdatePresence},
+        },
+        snowflake::Snowflake,
+    },
+    util::error::Error,
+};
 
 /// Context object that is passed to all parts of the bot
 /// It contains key information so that methods can create requests to discord, and also contains settings for those functions
@@ -9,9 +28,102 @@ pub struct Context {
     /// The token for the bot's instance
     pub token: String,
     /// The request sender for the instance's bot. Allows the user to make http requests
-    pub request_stream: Sender<RequestObject>,
+    pub request_stream: RequestSender,
     /// The settings for the bot's instance
     pub settings: Settings,
+    /// The id of the shard that received the event
+    pub shard_id: u64,
+    /// Sends commands to the gateway connections of the bot
+    pub gateway: GatewayHandle,
+    /// Stops the bot
+    pub shutdown: ShutdownHandle,
     /// The cache for the bot's instance
     pub cache: (), // TODO
-}
\ No newline at end of file
+}
+
+impl Context {
+    /**
+     * Updates the bot's presence on every shard
+     * @param status The bot's new status
+     * @param activities The activities that the bot is doing
+     * @param afk Whether the bot is afk
+     * @docs <https://discord.com/developers/docs/topics/gateway#update-presence>
+     */
+    pub fn set_presence(&self, status: StatusType, activities: Vec<Activity>, afk: bool) {
+        self.gateway.broadcast(UpdatePresence::new(status, activities, afk));
+    }
+
+    /// Gets the round trip latency of the gateway connection for the shard that received the event.
+    /// None if no heartbeat has been acknowledged yet
+    pub fn latency(&self) -> Option<Duration> {
+        self.gateway.latency(self.shard_id)
+    }
+
+    /**
+     * Requests members of a guild from the gateway, and waits for every Guild Members Chunk of the response.
+     * The request is sent through the shard that the guild is on, so that shard has to be run by this process.
+     * @param guild_id The guild to get members for
+     * @param filter Which members to get. Getting every member requires the GUILD_MEMBERS intent
+     * @param presences Whether to get the presences of the members. Requires the GUILD_PRESENCES intent
+     * @docs <https://discord.com/developers/docs/topics/gateway#request-guild-members>
+     */
+    pub async fn request_guild_members(
+        &self,
+        guild_id: Snowflake,
+        filter: MemberFilter,
+        presences: bool,
+    ) -> Result<GuildMembers, Error> {
+        self.gateway
+            .request_guild_members(guild_id, filter, presences)
+            .await
+    }
+
+    /// Gets a handle for making requests to endpoints that the library doesn't have a method for, with the settings of this context
+    pub fn http(&self) -> HttpHandle<'_> {
+        HttpHandle::new(self)
+    }
+
+    /**
+     * Gets a copy of the context where requests are cancelled if they don't get a response within the timeout.
+     * This overrides the request timeout in the settings for the requests that are made with it.
+     * @param timeout How long to wait for each response, including the time spent waiting for rate limits
+     */
+    pub fn with_timeout(&self, timeout: Duration) -> Context {
+        let mut ctx = self.clone();
+        ctx.settings.request_timeout = Some(timeout);
+        ctx
+    }
+
+    /**
+     * Gets a copy of the context where requests that fail are retried with the given policy, instead of the http client's policy.
+     * @param retry_policy Decides which failed requests are sent again, and how long to wait before each attempt
+     */
+    pub fn with_retry_policy(&self, retry_policy: RetryPolicy) -> Context {
+        let mut ctx = self.clone();
+        ctx.settings.retry_policy = Some(retry_policy);
+        ctx
+    }
+
+    /**
+     * Gets a copy of the context where requests are sent with the given priority.
+     * The priority is only used if the bot was created with a queue that sorts by priority, like `PriorityHttpQueue`
+     * @param priority How urgent the requests are
+     */
+    pub fn with_priority(&self, priority: RequestPriority) -> Context {
+        let mut ctx = self.clone();
+        ctx.settings.priority = Some(priority);
+        ctx
+    }
+
+    /**
+     * Gets a copy of the context where requests that change something, like kicking a member or editing a channel, are recorded in the audit log with the given reason.
+     * The reason is percent-encoded before it is sent, so it can contain any text
+     * @param reason The reason for the actions (up to 512 characters)
+     * @docs <https://discord.com/developers/docs/resources/audit-log>
+     */
+    pub fn with_reason(&self, reason: &str) -> Context {
+        let mut ctx = self.clone();
+        ctx.settings.audit_log_reason = Some(reason.to_string());
+        ctx
+    }
+}
@@ -11,7 +11,13 @@ use crate::core::interactions::handler::events::dispatch_payloads::{
     MessageReactionRemoveEmoji, ThreadListSync, ThreadMemberUpdate, ThreadMembersUpdate,
     TypingStart, VoiceServerUpdate, WebhooksUpdate,
 };
-use crate::core::interactions::{handler::events::ready_payload::ReadyPayloadData, typing::Interaction};
+use crate::core::interactions::handler::events::lifecycle_payloads::{
+    AllShardsReady, ShardConnecting, ShardDisconnected, ShardReady, ShardResumed,
+};
+use crate::core::interactions::{
+    handler::events::{ready_payload::ReadyPayloadData, Intents},
+    typing::Interaction,
+};
 
 use crate::discord::gateway::presence::PresenceUpdate;
 use crate::discord::resources::channel::{message::Message, Channel};
@@ -121,6 +127,7 @@ macro_rules! event_subscriptions {
         }
 
         #[doc="An enum containing all of the events that can be dispatched"]
+        #[derive(Clone, Copy, PartialEq, Eq, Debug)]
         pub enum Events {
             $(
                 $(#[$inner])*
@@ -184,15 +191,26 @@ event_subscriptions! {
          * 2. When a Guild becomes available again to the client.
          * 3. When the current user joins a new Guild.
          * The inner payload is a guild object, with all the extra fields specified.
+         * Use `GuildAvailable` or `GuildJoin` to only handle one of these.
          */
         const GuildCreate: Guild = "GUILD_CREATE";
+        /// Sent by the library when a guild that the bot is already in becomes available,
+        /// either while connecting, or after an outage. Derived from Guild Create
+        const GuildAvailable: Guild = "GUILD_AVAILABLE";
+        /// Sent by the library when the bot joins a new guild. Derived from Guild Create
+        const GuildJoin: Guild = "GUILD_JOIN";
         /// guild was updated
         const GuildUpdate: Guild = "GUILD_UPDATE";
         /// Sent when a guild becomes or was already unavailable due to an outage,
         /// or when the user leaves or is removed from a guild.
         /// The inner payload is an unavailable guild object.
         /// If the unavailable field is not set, the user was removed from the guild.
+        /// Use `GuildUnavailable` or `GuildLeave` to only handle one of these.
         const GuildDelete: UnavailableGuild = "GUILD_DELETE";
+        /// Sent by the library when a guild becomes unavailable due to an outage. Derived from Guild Delete
+        const GuildUnavailable: UnavailableGuild = "GUILD_UNAVAILABLE";
+        /// Sent by the library when the bot leaves or is removed from a guild. Derived from Guild Delete
+        const GuildLeave: UnavailableGuild = "GUILD_LEAVE";
         /// user was banned from a guild
         const GuildBanAdd: GuildBanAddRemove = "GUILD_BAN_ADD";
         /// user was unbanned from a guild
@@ -275,5 +293,98 @@ event_subscriptions! {
         const WebhooksUpdate: WebhooksUpdate = "WEBHOOKS_UPDATE";
         /// Triggered when the bot is fully connected to the gateway.
         const Ready: ReadyPayloadData = "READY"; 
+
+        //================
+        //   Lifecycle
+        //================
+
+        /// Sent by the library before a shard connects to the gateway for the first time
+        const ShardConnecting: ShardConnecting = "SHARD_CONNECTING";
+        /// Sent by the library before a shard connects to the gateway again after its connection ended
+        const ShardReconnecting: ShardConnecting = "SHARD_RECONNECTING";
+        /// Sent by the library when a shard has identified and received the Ready event
+        const ShardReady: ShardReady = "SHARD_READY";
+        /// Sent by the library when a shard has resumed its session
+        const ShardResumed: ShardResumed = "SHARD_RESUMED";
+        /// Sent by the library when a shard's connection to the gateway ends
+        const ShardDisconnected: ShardDisconnected = "SHARD_DISCONNECTED";
+        /// Sent by the library once every shard that is run by this process is ready
+        const AllShardsReady: AllShardsReady = "ALL_SHARDS_READY";
+    }
+}
+
+impl Events {
+    /// Gets the gateway intents that deliver this event. Receiving any one of them is enough to receive the event,
+    /// but some (like `MessageCreate`) will only be received from guilds or from DMs if just one is set.
+    /// Events that are always sent, like `Ready`, return no intents.
+    /// @docs <https://discord.com/developers/docs/topics/gateway#list-of-intents>
+    pub fn intents(&self) -> Intents {
+        match self {
+            Events::GuildCreate
+            | Events::GuildAvailable
+            | Events::GuildJoin
+            | Events::GuildUpdate
+            | Events::GuildDelete
+            | Events::GuildUnavailable
+            | Events::GuildLeave
+            | Events::GuildRoleCreate
+            | Events::GuildRoleUpdate
+            | Events::GuildRoleDelete
+            | Events::ChannelCreate
+            | Events::ChannelUpdate
+            | Events::ChannelDelete
+            | Events::ThreadCreate
+            | Events::ThreadUpdate
+            | Events::ThreadDelete
+            | Events::ThreadListSync
+            | Events::ThreadMemberUpdate
+            | Events::StageInstanceCreate
+            | Events::StageInstanceUpdate
+            | Events::StageInstanceDelete => Intents::GUILDS,
+            Events::ChannelPinsUpdate => Intents::GUILDS | Intents::DIRECT_MESSAGES,
+            Events::ThreadMembersUpdate => Intents::GUILDS | Intents::GUILD_MEMBERS,
+            Events::GuildMemberAdd | Events::GuildMemberUpdate | Events::GuildMemberRemove => {
+                Intents::GUILD_MEMBERS
+            }
+            Events::GuildBanAdd | Events::GuildBanRemove => Intents::GUILD_BANS,
+            Events::GuildEmojisUpdate | Events::GuildStickersUpdate => {
+                Intents::GUILD_EMOJIS_AND_STICKERS
+            }
+            Events::GuildIntegrationsUpdate
+            | Events::IntegrationCreate
+            | Events::IntegrationUpdate
+            | Events::IntegrationDelete => Intents::GUILD_INTEGRATIONS,
+            Events::WebhooksUpdate => Intents::GUILD_WEBHOOKS,
+            Events::InviteCreate | Events::InviteDelete => Intents::GUILD_INVITES,
+            Events::VoiceStateUpdate => Intents::GUILD_VOICE_STATES,
+            Events::PresenceUpdate => Intents::GUILD_PRESENCES,
+            Events::MessageCreate | Events::MessageUpdate | Events::MessageDelete => {
+                Intents::GUILD_MESSAGES | Intents::DIRECT_MESSAGES
+            }
+            Events::MessageDeleteBulk => Intents::GUILD_MESSAGES,
+            Events::MessageReactionAdd
+            | Events::MessageReactionRemove
+            | Events::MessageReactionRemoveAll
+            | Events::MessageReactionRemoveEmoji => {
+                Intents::GUILD_MESSAGE_REACTIONS | Intents::DIRECT_MESSAGE_REACTIONS
+            }
+            Events::TypingStart => Intents::GUILD_MESSAGE_TYPING | Intents::DIRECT_MESSAGE_TYPING,
+            Events::GuildScheduledEventCreate
+            | Events::GuildScheduledEventUpdate
+            | Events::GuildScheduledEventDelete
+            | Events::GuildScheduledEventUserAdd
+            | Events::GuildScheduledEventUserRemove => Intents::GUILD_SCHEDULED_EVENTS,
+            Events::GuildMembersChunk
+            | Events::InteractionCtx
+            | Events::UserUpdate
+            | Events::VoiceServerUpdate
+            | Events::Ready
+            | Events::ShardConnecting
+            | Events::ShardReconnecting
+            | Events::ShardReady
+            | Events::ShardResumed
+            | Events::ShardDisconnected
+            | Events::AllShardsReady => Intents::empty(),
+        }
     }
 }
@@ -0,0 +1,143 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use crate::discord::{resources::guild::guild_object::UnavailableGuild, snowflake::Snowflake};
+
+/// Keeps track of the guilds that the bot is in, so that Guild Create and Guild Delete events can be told apart.
+/// A Guild Create is a join if the guild wasn't in any Ready event and hasn't been seen before, otherwise the guild has just become available.
+/// A resumed session has no Ready event, so a guild that hasn't been seen is also available if the bot joined it before the tracker started.
+/// A Guild Delete is an outage if the guild is marked as unavailable, otherwise the bot has left or been removed from the guild.
+pub(crate) struct GuildTracker {
+    /// The ids of the guilds that the bot is in, across every shard
+    guilds: HashSet<Snowflake>,
+    /// When the tracker started, which is before any shard connected
+    started_at: DateTime<Utc>,
+}
+
+impl GuildTracker {
+    /// @param started_at Guilds that were joined before this time aren't reported as joins
+    pub(crate) fn new(started_at: DateTime<Utc>) -> Self {
+        Self {
+            guilds: HashSet::new(),
+            started_at,
+        }
+    }
+
+    /**
+     * Updates the tracked guilds with an event from the gateway
+     * @param event The name of the event
+     * @param data The data of the event
+     * @return The name of the event that is derived from it, if there is one
+     */
+    pub(crate) fn track(&mut self, event: &str, data: &Value) -> Option<&'static str> {
+        match event {
+            "READY" => {
+                // Every guild in the ready event starts out unavailable, and is then sent in a Guild Create
+                let guilds: Vec<UnavailableGuild> =
+                    serde_json::from_value(data["guilds"].clone()).ok()?;
+                self.guilds.extend(guilds.into_iter().map(|guild| guild.id));
+                None
+            }
+            "GUILD_CREATE" => {
+                let id = serde_json::from_value(data["id"].clone()).ok()?;
+                let joined_before_start = data["joined_at"]
+                    .as_str()
+                    .and_then(|joined_at| DateTime::parse_from_rfc3339(joined_at).ok())
+                    .map_or(false, |joined_at| joined_at < self.started_at);
+                // After a resume, a guild that the bot was already in is sent when it recovers from an outage
+                let recovered = joined_before_start && !data["unavailable"].as_bool().unwrap_or(false);
+
+                if self.guilds.insert(id) && !recovered {
+                    Some("GUILD_JOIN")
+                } else {
+                    Some("GUILD_AVAILABLE")
+                }
+            }
+            "GUILD_DELETE" => {
+                let guild: UnavailableGuild = serde_json::from_value(data.clone()).ok()?;
+                if guild.unavailable {
+                    // The bot is still in the guild. After a resume there is no Ready event, so this might be the first time it is seen
+                    self.guilds.insert(guild.id);
+                    Some("GUILD_UNAVAILABLE")
+                } else {
+                    self.guilds.remove(&guild.id);
+                    Some("GUILD_LEAVE")
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Default for GuildTracker {
+    fn default() -> Self {
+        GuildTracker::new(Utc::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use serde_json::{json, Value};
+
+    use super::GuildTracker;
+
+    /// A Guild Create for a guild that the bot joined at the given time
+    fn guild_create(id: &str, joined_at: chrono::DateTime<Utc>) -> Value {
+        json!({"id": id, "name": "Mason's Test Server", "joined_at": joined_at.to_rfc3339(), "unavailable": false})
+    }
+
+    #[test]
+    fn guilds_from_ready_become_available() {
+        let mut tracker = GuildTracker::default();
+        tracker.track("READY", &json!({"guilds": [{"id": "41771983423143937", "unavailable": true}]}));
+
+        let event = tracker.track("GUILD_CREATE", &guild_create("41771983423143937", Utc::now()));
+        assert_eq!(event, Some("GUILD_AVAILABLE"));
+    }
+
+    #[test]
+    fn unseen_guilds_are_joins() {
+        let mut tracker = GuildTracker::new(Utc::now() - Duration::minutes(5));
+
+        let event = tracker.track("GUILD_CREATE", &guild_create("41771983423143937", Utc::now()));
+        assert_eq!(event, Some("GUILD_JOIN"));
+        // Once it has been seen, it becomes available again after an outage
+        let event = tracker.track("GUILD_CREATE", &guild_create("41771983423143937", Utc::now()));
+        assert_eq!(event, Some("GUILD_AVAILABLE"));
+    }
+
+    #[test]
+    fn guilds_joined_before_a_resume_become_available() {
+        // There is no Ready event when a stored session is resumed
+        let mut tracker = GuildTracker::default();
+
+        let joined_at = Utc::now() - Duration::days(30);
+        let event = tracker.track("GUILD_CREATE", &guild_create("41771983423143937", joined_at));
+        assert_eq!(event, Some("GUILD_AVAILABLE"));
+    }
+
+    #[test]
+    fn unavailable_guild_deletes_are_outages() {
+        let mut tracker = GuildTracker::default();
+
+        let event = tracker.track("GUILD_DELETE", &json!({"id": "41771983423143937", "unavailable": true}));
+        assert_eq!(event, Some("GUILD_UNAVAILABLE"));
+        let event = tracker.track("GUILD_CREATE", &guild_create("41771983423143937", Utc::now()));
+        assert_eq!(event, Some("GUILD_AVAILABLE"));
+    }
+
+    #[test]
+    fn other_guild_deletes_are_leaves() {
+        let mut tracker = GuildTracker::default();
+        tracker.track("GUILD_CREATE", &guild_create("41771983423143937", Utc::now()));
+
+        let event = tracker.track("GUILD_DELETE", &json!({"id": "41771983423143937"}));
+        assert_eq!(event, Some("GUILD_LEAVE"));
+        // The bot was removed, so being added back is a join
+        let event = tracker.track("GUILD_CREATE", &guild_create("41771983423143937", Utc::now()));
+        assert_eq!(event, Some("GUILD_JOIN"));
+    }
+}
@@ -3,6 +3,8 @@ pub mod context;
 
 pub mod traits;
 pub mod event_dispatcher;
+pub(crate) mod guild_tracker;
 pub mod interaction_router;
 pub mod observer;
-pub mod option_builder;
\ No newline at end of file
+pub mod option_builder;
+pub mod shutdown;
\ No newline at end of file
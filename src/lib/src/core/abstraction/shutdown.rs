@@ -0,0 +1,149 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    thread,
+    time::Duration,
+};
+
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender};
+
+/// Stops the bot. Once shutdown is requested, every gateway connection is closed, no more events are handled,
+/// and `Bot::listen` returns after the requests that are still queued have been sent (or the shutdown timeout has passed).
+/// It can be cloned and used from anywhere, including from inside a handler through the context.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    shared: Arc<SharedShutdown>,
+    /// Disconnected when shutdown is requested, which wakes up everything that is waiting on it
+    signal: Receiver<()>,
+}
+
+/// The part of the shutdown handle that is shared between its clones
+struct SharedShutdown {
+    /// Set to true once shutdown is requested
+    requested: AtomicBool,
+    /// The only sender of the signal. It is dropped when shutdown is requested
+    closer: Mutex<Option<Sender<()>>>,
+    /// The async tasks that are waiting for shutdown
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        let (closer, signal) = bounded(0);
+        Self {
+            shared: Arc::new(SharedShutdown {
+                requested: AtomicBool::new(false),
+                closer: Mutex::new(Some(closer)),
+                wakers: Mutex::new(Vec::new()),
+            }),
+            signal,
+        }
+    }
+}
+
+impl ShutdownHandle {
+    /// Requests that the bot shuts down. Calling this more than once has no extra effect
+    pub fn shutdown(&self) {
+        if self.shared.requested.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.shared.closer.lock().unwrap().take();
+        for waker in self.shared.wakers.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+
+    /// Whether shutdown has been requested
+    pub fn is_requested(&self) -> bool {
+        self.shared.requested.load(Ordering::SeqCst)
+    }
+
+    /// Requests shutdown when the process receives SIGTERM or Ctrl+C.
+    /// The signals are listened for on their own thread, so this can be called from outside of an async runtime
+    pub fn shutdown_on_signal(&self) {
+        let handle = self.clone();
+        thread::Builder::new()
+            .name("Shutdown_Signal_Handler".to_string())
+            .spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap();
+                runtime.block_on(wait_for_signal());
+                handle.shutdown();
+            })
+            .unwrap();
+    }
+
+    /// Waits until shutdown is requested
+    pub(crate) fn wait(&self) -> WaitForShutdown<'_> {
+        WaitForShutdown { handle: self }
+    }
+
+    /**
+     * Blocks the thread until shutdown is requested, or the timeout has passed
+     * @return Whether shutdown was requested
+     */
+    pub(crate) fn wait_timeout(&self, timeout: Duration) -> bool {
+        match self.signal.recv_timeout(timeout) {
+            Err(RecvTimeoutError::Timeout) => self.is_requested(),
+            _ => true,
+        }
+    }
+
+    /// Gets the channel that is disconnected when shutdown is requested
+    pub(crate) fn signal(&self) -> Receiver<()> {
+        self.signal.clone()
+    }
+}
+
+/// A future that finishes once shutdown is requested
+pub(crate) struct WaitForShutdown<'a> {
+    handle: &'a ShutdownHandle,
+}
+
+impl Future for WaitForShutdown<'_> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.handle.is_requested() {
+            return Poll::Ready(());
+        }
+        {
+            let mut wakers = self.handle.shared.wakers.lock().unwrap();
+            // The future is made again every time a shard waits for its next message, so the same task is only stored once
+            if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+                wakers.push(cx.waker().clone());
+            }
+        }
+        // Shutdown might have been requested before the waker was stored
+        if self.handle.is_requested() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// Waits for SIGTERM or Ctrl+C
+#[cfg(unix)]
+async fn wait_for_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut sigterm = signal(SignalKind::terminate()).unwrap();
+    tokio::select! {
+        _ = sigterm.recv() => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+}
+
+/// Waits for Ctrl+C
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    tokio::signal::ctrl_c().await.ok();
+}
@@ -0,0 +1,62 @@
+use hyper::{Body, Method, Request};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{core::abstraction::context::Context, util::error::Error};
+
+use super::{
+    rate_limit_client::{send_request, RequestRoute},
+    transport::TransportError,
+};
+
+/// Makes requests to endpoints that the library doesn't have a method for.
+/// Requests go through the same rate limited queue as every other request, and use the settings of the context that it came from,
+/// so `ctx.with_timeout(..).http()` sends requests with that timeout.
+/// Get one with `ctx.http()`
+pub struct HttpHandle<'a> {
+    ctx: &'a Context,
+}
+
+impl<'a> HttpHandle<'a> {
+    pub(crate) fn new(ctx: &'a Context) -> Self {
+        HttpHandle { ctx }
+    }
+
+    /**
+     * Sends a request to the api, and parses the response.
+     * The bucket of the request is worked out from its path, and the bot's token is added to it.
+     * Use `()` as the response type for endpoints that respond with 204 No Content
+     * @param method The HTTP method of the request
+     * @param path The path of the endpoint, without the base url. Like `/channels/123/pins/456`
+     * @param body The json body of the request, if it has one
+     * @return The response from discord
+     */
+    pub async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<T, Error> {
+        let route = RequestRoute::from_path(path);
+        let path = format!("/{}", path.trim_start_matches('/'));
+
+        let mut request_builder = Request::builder()
+            .method(method.clone())
+            .uri(format!("{}{}", self.ctx.settings.get_base_url(), path));
+        let body = match body {
+            Some(body) => {
+                request_builder = request_builder.header("content-type", "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let request = request_builder
+            .body(body)
+            .map_err(|e| Error::Request {
+                route: format!("{} {}", method, path),
+                source: TransportError::new(e),
+            })?;
+
+        send_request(self.ctx.clone(), route, request).await
+    }
+}
@@ -1,5 +1,10 @@
+pub mod http_handle;
+pub(crate) mod multipart;
 pub mod rate_limit_client;
 mod request_bucket;
 mod request_future;
 pub mod request_queue;
 mod request_thread;
+pub mod retry_policy;
+pub mod route;
+pub mod transport;
@@ -0,0 +1,212 @@
+use hyper::Body;
+use rand::{distributions::Alphanumeric, Rng};
+use serde_json::{json, Value};
+
+use crate::discord::resources::channel::attachment::AttachmentFile;
+
+/**
+ * Adds a partial attachment object for each file to the `attachments` of a message payload, so that discord knows which upload is which.
+ * The id of each attachment is the index of its file, which matches the `files[n]` part that it is uploaded in
+ * @param message The json of the message that the files are sent with
+ * @param files The files that are uploaded with the message
+ * @docs <https://discord.com/developers/docs/reference#uploading-files>
+ */
+pub(crate) fn add_attachments(message: &mut Value, files: &[AttachmentFile]) {
+    if files.is_empty() {
+        return;
+    }
+    let message = match message.as_object_mut() {
+        Some(message) => message,
+        None => return,
+    };
+    let attachments = message
+        .entry("attachments")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    if !attachments.is_array() {
+        *attachments = Value::Array(Vec::new());
+    }
+    if let Value::Array(attachments) = attachments {
+        attachments.extend(files.iter().enumerate().map(|(id, file)| {
+            json!({
+                "id": id,
+                "filename": file.filename,
+                "description": file.description,
+            })
+        }));
+    }
+}
+
+/**
+ * Creates the body of a request that sends a message. It is json if there aren't any files,
+ * and otherwise it is multipart/form-data with the json in the `payload_json` part and each file in a `files[n]` part
+ * @param payload The json body of the request
+ * @param files The files to upload with the request
+ * @return The content type of the body, and the body
+ */
+pub(crate) fn message_body(payload: &Value, files: &[AttachmentFile]) -> (String, Body) {
+    if files.is_empty() {
+        return ("application/json".to_string(), Body::from(payload.to_string()));
+    }
+
+    let boundary: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+
+    let mut body = Vec::new();
+    body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+    body.extend_from_slice(b"Content-Disposition: form-data; name=\"payload_json\"\r\n");
+    body.extend_from_slice(b"Content-Type: application/json\r\n\r\n");
+    body.extend_from_slice(payload.to_string().as_bytes());
+    body.extend_from_slice(b"\r\n");
+
+    for (i, file) in files.iter().enumerate() {
+        body.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        body.extend_from_slice(
+            format!(
+                "Content-Disposition: form-data; name=\"files[{}]\"; filename=\"{}\"\r\n",
+                i,
+                escape_filename(&file.filename)
+            )
+            .as_bytes(),
+        );
+        body.extend_from_slice(b"Content-Type: application/octet-stream\r\n\r\n");
+        body.extend_from_slice(&file.data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+
+    (
+        format!("multipart/form-data; boundary={}", boundary),
+        Body::from(body),
+    )
+}
+
+/// Escapes the characters of a file name that would end the quoted filename in a Content-Disposition header
+fn escape_filename(filename: &str) -> String {
+    filename
+        .replace('\\', "\\\\")
+        .replace('"', "%22")
+        .replace(['\r', '\n'], "")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::discord::resources::channel::attachment::AttachmentFile;
+
+    use super::{add_attachments, message_body};
+
+    /// A part of a multipart body
+    struct Part {
+        headers: Vec<String>,
+        body: Vec<u8>,
+    }
+
+    /// Splits a multipart body into its parts
+    async fn parse(content_type: &str, body: hyper::Body) -> Vec<Part> {
+        let boundary = content_type
+            .strip_prefix("multipart/form-data; boundary=")
+            .unwrap();
+        let body = hyper::body::to_bytes(body).await.unwrap().to_vec();
+        let delimiter = format!("--{}", boundary).into_bytes();
+
+        let mut parts = Vec::new();
+        let mut rest = &body[..];
+        assert!(rest.starts_with(&delimiter));
+        loop {
+            rest = &rest[delimiter.len()..];
+            if rest == b"--\r\n" {
+                return parts;
+            }
+            rest = rest.strip_prefix(b"\r\n").unwrap();
+            let end = rest
+                .windows(delimiter.len() + 2)
+                .position(|window| window[..2] == *b"\r\n" && window[2..] == delimiter[..])
+                .unwrap();
+            let part = &rest[..end];
+            let split = part.windows(4).position(|window| window == b"\r\n\r\n").unwrap();
+            parts.push(Part {
+                headers: String::from_utf8(part[..split].to_vec())
+                    .unwrap()
+                    .split("\r\n")
+                    .map(String::from)
+                    .collect(),
+                body: part[split + 4..].to_vec(),
+            });
+            rest = &rest[end + 2..];
+        }
+    }
+
+    #[tokio::test]
+    async fn sends_json_without_files() {
+        let payload = json!({"content": "hi"});
+        let (content_type, body) = message_body(&payload, &[]);
+        assert_eq!(content_type, "application/json");
+        assert_eq!(hyper::body::to_bytes(body).await.unwrap(), payload.to_string());
+    }
+
+    #[tokio::test]
+    async fn uploads_files_with_the_payload() {
+        let mut files = vec![
+            AttachmentFile::from_bytes("log.txt", "line 1\r\n--not a boundary"),
+            AttachmentFile::from_bytes("image.png", vec![0x89, b'P', b'N', b'G']),
+        ];
+        files[1].description = Some("A picture".to_string());
+        let mut payload = json!({"content": "Here are the files"});
+        add_attachments(&mut payload, &files);
+
+        let (content_type, body) = message_body(&payload, &files);
+        let parts = parse(&content_type, body).await;
+        assert_eq!(parts.len(), 3);
+
+        assert_eq!(parts[0].headers[0], "Content-Disposition: form-data; name=\"payload_json\"");
+        let sent: serde_json::Value = serde_json::from_slice(&parts[0].body).unwrap();
+        assert_eq!(sent, payload);
+        assert_eq!(
+            sent["attachments"],
+            json!([
+                {"id": 0, "filename": "log.txt", "description": null},
+                {"id": 1, "filename": "image.png", "description": "A picture"},
+            ])
+        );
+
+        // Each attachment's id is the index of the part that its file is uploaded in
+        for (id, file) in files.iter().enumerate() {
+            let part = &parts[id + 1];
+            assert_eq!(
+                part.headers[0],
+                format!(
+                    "Content-Disposition: form-data; name=\"files[{}]\"; filename=\"{}\"",
+                    sent["attachments"][id]["id"], file.filename
+                )
+            );
+            assert_eq!(part.body, file.data.to_vec());
+        }
+    }
+
+    #[test]
+    fn keeps_existing_attachments() {
+        let mut payload = json!({"attachments": [{"id": "41771983423143937"}]});
+        add_attachments(&mut payload, &[AttachmentFile::from_bytes("a.txt", "a")]);
+        assert_eq!(payload["attachments"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn escapes_file_names() {
+        let files = [AttachmentFile::from_bytes("a\"b\r\nContent-Type: text/html\\.txt", "a")];
+        let (content_type, body) = message_body(&json!({}), &files);
+        let parts = parse(&content_type, body).await;
+
+        // The name can't end the quoted string or start a new header
+        assert_eq!(
+            parts[1].headers,
+            vec![
+                "Content-Disposition: form-data; name=\"files[0]\"; filename=\"a%22bContent-Type: text/html\\\\.txt\"",
+                "Content-Type: application/octet-stream",
+            ]
+        );
+    }
+}
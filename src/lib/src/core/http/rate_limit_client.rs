@@ -1,18 +1,33 @@
-use hyper::{body::Body, header::AUTHORIZATION, Request};
+use hyper::{
+    body::{Body, Bytes},
+    header::{HeaderValue, AUTHORIZATION},
+    Request,
+};
 use serde::de::DeserializeOwned;
 
 use crate::{
     core::abstraction::context::Context,
-    util::{error::Error, logger::print_debug},
+    util::{
+        error::{Error, HttpError},
+        logger::print_debug,
+        requests::percent_encode,
+    },
 };
 
 use super::{
-    request_future::{self},
+    request_future::{self, SharedRequest},
     request_queue::HttpQueue,
     request_thread,
+    retry_policy::RetryPolicy,
+    transport::{HttpTransport, HyperTransport},
+};
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, RwLock,
 };
 
-use crossbeam_channel::{unbounded, Receiver, Sender};
+use crossbeam_channel::{unbounded, Receiver, SendError, Sender};
 
 #[derive(Hash, Eq, PartialEq, Debug, Clone)]
 pub struct RequestRoute {
@@ -24,28 +39,71 @@ unsafe impl Send for RequestRoute {}
 
 pub struct RequestObject {
     pub route: RequestRoute,
-    pub future: *mut request_future::HttpFuture,
+    /// The state of the request, which is shared with the future that is waiting for it
+    pub future: SharedRequest,
 }
 
-unsafe impl Send for RequestObject {}
-
 impl RequestObject {
-    pub fn new(route: RequestRoute, future: *mut request_future::HttpFuture) -> RequestObject {
+    pub fn new(route: RequestRoute, future: SharedRequest) -> RequestObject {
         RequestObject { route, future }
     }
 }
 
+/// Sends requests to the request thread. Requests are counted as pending from the moment they are sent,
+/// so that they are never missed while they move from the channel to the request thread
+#[derive(Clone)]
+pub struct RequestSender {
+    sender: Sender<RequestObject>,
+    /// The number of requests that have been sent but not finished
+    in_progress: Arc<AtomicUsize>,
+}
+
+impl RequestSender {
+    /// Sends a request to the request thread. Fails if the request thread has stopped
+    pub fn send(&self, request: RequestObject) -> Result<(), SendError<RequestObject>> {
+        self.in_progress.fetch_add(1, Ordering::SeqCst);
+        let result = self.sender.send(request);
+        if result.is_err() {
+            self.in_progress.fetch_sub(1, Ordering::SeqCst);
+        }
+        result
+    }
+}
+
 pub struct RLClient {
     sender: Sender<RequestObject>,
+    /// The number of requests that have been sent to the request thread but not finished
+    in_progress: Arc<AtomicUsize>,
+    /// The retry policy for requests that don't have their own
+    retry_policy: Arc<RwLock<RetryPolicy>>,
+    /// Sends the requests
+    transport: Arc<dyn HttpTransport>,
 }
 
 impl RLClient {
     pub fn new<T>(queue: T) -> RLClient
+    where
+        T: HttpQueue + Send + 'static,
+    {
+        RLClient::with_transport(queue, HyperTransport::new())
+    }
+
+    /**
+     * Creates a client that sends its requests through the given transport, instead of sending them to discord with hyper
+     * @param queue Decides the order that requests are sent in
+     * @param transport Sends the requests
+     */
+    pub fn with_transport<T>(queue: T, transport: impl HttpTransport + 'static) -> RLClient
     where
         T: HttpQueue + Send + 'static,
     {
         let (s, r) = unbounded();
-        let mut c = RLClient { sender: s };
+        let mut c = RLClient {
+            sender: s,
+            in_progress: Arc::new(AtomicUsize::new(0)),
+            retry_policy: Arc::new(RwLock::new(RetryPolicy::default())),
+            transport: Arc::new(transport),
+        };
         c.spawn_req_thread::<T>(queue, r);
         c
     }
@@ -58,16 +116,110 @@ impl RLClient {
     where
         T: HttpQueue + Send + 'static,
     {
-        request_thread::create_thread::<T>(queue, receiver);
+        request_thread::create_thread::<T>(
+            queue,
+            receiver,
+            self.in_progress.clone(),
+            self.retry_policy.clone(),
+            self.transport.clone(),
+        );
+    }
+
+    /// Set the retry policy for requests that fail. It can be changed for a single request with `ctx.with_retry_policy`
+    pub fn set_retry_policy(&self, retry_policy: RetryPolicy) {
+        *self.retry_policy.write().unwrap() = retry_policy;
+    }
+
+    pub fn get_req_sender(&self) -> RequestSender {
+        RequestSender {
+            sender: self.sender.clone(),
+            in_progress: self.in_progress.clone(),
+        }
+    }
+
+    /// Gets the number of requests that have been sent to the client but haven't received a response yet
+    pub fn pending_requests(&self) -> usize {
+        self.in_progress.load(Ordering::SeqCst)
+    }
+}
+
+/**
+ * Waits for the response to a request, until the context's request timeout has passed, and reads its body.
+ * If it times out, the future is dropped, which cancels the request
+ * @param route The method and path of the request, which is added to errors
+ * @return The body of the response, or the error that discord responded with
+ */
+async fn wait_for_response(
+    ctx: &Context,
+    route: String,
+    future: request_future::HttpFuture,
+) -> Result<Bytes, Error> {
+    let response = match ctx.settings.request_timeout {
+        Some(timeout) => match async_std::future::timeout(timeout, future).await {
+            Ok(response) => response,
+            Err(_) => return Err(log_error(ctx, Error::Timeout { route, timeout })),
+        },
+        None => future.await,
+    };
+    let response = match response {
+        Ok(response) => response,
+        Err(source) => return Err(log_error(ctx, Error::Request { route, source })),
+    };
+
+    let status = response.status();
+    let body = match hyper::body::to_bytes(response).await {
+        Ok(body) => body,
+        Err(source) => {
+            return Err(log_error(
+                ctx,
+                Error::Request {
+                    route,
+                    source: source.into(),
+                },
+            ))
+        }
+    };
+    if !status.is_success() {
+        return Err(log_error(
+            ctx,
+            Error::Http(HttpError::from_response(route, status.as_u16(), &body)),
+        ));
+    }
+    Ok(body)
+}
+
+/// Prints an error if debugging is enabled, and then returns it
+fn log_error(ctx: &Context, error: Error) -> Error {
+    if ctx.settings.debug {
+        print_debug("REQUEST", format!("Error: {}", error));
     }
+    error
+}
 
-    pub fn get_req_sender(&self) -> Sender<RequestObject> {
-        self.sender.clone()
+/// The header that discord records as the reason for an action in the audit log
+const AUDIT_LOG_REASON: &str = "X-Audit-Log-Reason";
+
+/// Adds the bot's token to a request, and the audit log reason in the context's settings if the request changes something
+fn add_headers(ctx: &Context, request: &mut Request<Body>) {
+    let mutating = !request.method().is_safe();
+    let headers = request.headers_mut();
+    headers.insert(AUTHORIZATION, format!("Bot {}", ctx.token).parse().unwrap());
+
+    if let Some(reason) = ctx.settings.audit_log_reason.as_ref().filter(|_| mutating) {
+        // The reason is percent-encoded, so it is always a valid header value
+        let reason = HeaderValue::from_str(&percent_encode(reason)).unwrap();
+        headers.insert(AUDIT_LOG_REASON, reason);
     }
 }
 
+/// Gets the method and path of a request, which is used to describe it in errors
+fn describe_request(request: &Request<Body>) -> String {
+    format!("{} {}", request.method(), request.uri().path())
+}
+
 /**
  * Send a request. This will queue the request and then execute when it is able to.
+ * It is cancelled if it doesn't receive a response before the context's request timeout.
  *
  * @param route The route identifier that the request belongs to
  * @param request The request to send
@@ -78,42 +230,32 @@ pub async fn send_request<T: DeserializeOwned>(
     route: RequestRoute,
     mut request: Request<Body>,
 ) -> Result<T, Error> {
-    request
-        .headers_mut()
-        .insert(AUTHORIZATION, format!("Bot {}", ctx.token).parse().unwrap());
+    add_headers(&ctx, &mut request);
 
-    let mut future = request_future::HttpFuture::new(request);
+    let route_name = describe_request(&request);
+    let future = request_future::HttpFuture::new(
+        request,
+        ctx.settings.retry_policy.clone(),
+        ctx.settings.priority.unwrap_or_default(),
+    );
     // TODO Maybe use req_thread.unpark() to reduce cpu load while the thread is waiting for requests.
     // This would have the downside of increasing the power required make a request since we have to attempt to unpark it every time.
     // We could maybe get around this by having a parked flag, but this would require a mutex which also increases the power required.
     ctx.request_stream
-        .send(RequestObject::new(route, &mut future as *mut _))
+        .send(RequestObject::new(route, future.state()))
         .unwrap();
 
-    let res = match future.await {
-        Ok(res) => res,
-        Err(e) => {
-            if ctx.settings.debug {
-                print_debug("REQUEST", format!("Error: {:?}", e));
-            }
-            return Err(Error::new(
-                format!("{:?}", e),
-                crate::util::error::ErrorTypes::REQUEST,
-            ));
-        }
-    };
-    let bytes = hyper::body::to_bytes(res).await.unwrap();
+    let bytes = wait_for_response(&ctx, route_name.clone(), future).await?;
+    // Responses without a body, like 204 No Content, are parsed as null so that they can be read into `()` or an Option
+    let bytes: &[u8] = if bytes.is_empty() { b"null" } else { &bytes };
 
-    serde_json::from_slice::<T>(&bytes.to_vec()).map_err(|e| {
-        if ctx.settings.debug {
-            print_debug("REQUEST", format!("Error: {:?}", e));
-        }
-        Error::new(format!("{:?}", e), crate::util::error::ErrorTypes::PARSE)
-    })
+    serde_json::from_slice::<T>(bytes)
+        .map_err(|source| log_error(&ctx, Error::Parse { route: route_name, source }))
 }
 
 /**
  * Send a request. This will queue the request and then execute when it is able to.
+ * It is cancelled if it doesn't receive a response before the context's request timeout.
  * This function will not try to parse the response
  * @param route The route identifier that the request belongs to
  * @param request The request to send
@@ -124,28 +266,20 @@ pub async fn send_request_noparse(
     route: RequestRoute,
     mut request: Request<Body>,
 ) -> Result<(), Error> {
-    request
-        .headers_mut()
-        .insert(AUTHORIZATION, format!("Bot {}", ctx.token).parse().unwrap());
+    add_headers(&ctx, &mut request);
 
-    let mut future = request_future::HttpFuture::new(request);
+    let route_name = describe_request(&request);
+    let future = request_future::HttpFuture::new(
+        request,
+        ctx.settings.retry_policy.clone(),
+        ctx.settings.priority.unwrap_or_default(),
+    );
     // TODO Maybe use req_thread.unpark() to reduce cpu load while the thread is waiting for requests.
     // This would have the downside of increasing the power required make a request since we have to attempt to unpark it every time.
     // We could maybe get around this by having a parked flag, but this would require a mutex which also increases the power required.
     ctx.request_stream
-        .send(RequestObject::new(route, &mut future as *mut _))
+        .send(RequestObject::new(route, future.state()))
         .unwrap();
 
-    match future.await {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            if ctx.settings.debug {
-                print_debug("REQUEST", format!("Error: {:?}", e));
-            }
-            return Err(Error::new(
-                format!("{:?}", e),
-                crate::util::error::ErrorTypes::REQUEST,
-            ));
-        }
-    }
+    wait_for_response(&ctx, route_name, future).await.map(|_| ())
 }
@@ -1,3 +1,5 @@
+use std::time::Instant;
+
 #[derive(PartialEq)]
 
 /// Contains information on an http bucket so that the route can be rate limited
@@ -7,7 +9,7 @@ pub struct Bucket {
     /// The number of requests that are remaining in the bucket
     pub remaining_requests: i32,
     /// The time that the buckets rate limit will reset
-    pub reset_at: i64,
+    pub reset_at: Instant,
 }
 
 impl Bucket {
@@ -15,7 +17,7 @@ impl Bucket {
         Self {
             max_requests: 1,
             remaining_requests: 1,
-            reset_at: 0,
+            reset_at: Instant::now(),
         }
     }
 }
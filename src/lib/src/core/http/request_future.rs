@@ -3,13 +3,20 @@ use std::{
     pin::Pin,
     sync::{Arc, Mutex},
     task::{Context, Poll, Waker},
+    time::Instant,
 };
 
-use hyper::{Body, Error, Request};
+use hyper::{body::Bytes, header::HeaderMap, Body, Method, Request, Uri};
+
+use super::{request_queue::RequestPriority, retry_policy::RetryPolicy, transport::TransportError};
+
+/// The state of a request, which is shared between the future that waits for it and the http client that sends it
+pub type SharedRequest = Arc<Mutex<RequestState>>;
 
 /// This future is used by the http client to transfer data about a request between threads.
-/// The future will initially be send to the http client, and then the http client will make the request, send the request response to the future,
+/// The future's state will initially be sent to the http client, and then the http client will make the request, send the request response to the state,
 /// and then wake the future up. This will unblock the request method, and then the future will unblock.
+/// If the future is dropped before it has a response, the request is cancelled.
 pub struct HttpFuture {
     /// State of the request
     pub shared_state: Arc<Mutex<RequestState>>,
@@ -20,16 +27,41 @@ pub struct RequestState {
     /// The request
     pub request: Option<Request<Body>>,
 
+    /// The request as it was sent, so that it can be sent again if it is rate limited
+    pub sent: Option<RequestParts>,
+
     /// The response that gets created once it is done
-    pub response: Option<Result<hyper::Response<Body>, Error>>,
+    pub response: Option<Result<hyper::Response<Body>, TransportError>>,
 
     /// Signals that the request has finished
     pub waker: Option<Waker>,
+
+    /// Set when the future is dropped, so that the request isn't sent if it is still queued, and its response is ignored if it isn't
+    pub cancelled: bool,
+
+    /// Overrides the client's retry policy for this request
+    pub retry_policy: Option<RetryPolicy>,
+
+    /// The number of times that the request has been sent, not counting attempts that were rate limited
+    pub attempts: u32,
+
+    /// How urgent the request is, which is used by queues that sort by priority
+    pub priority: RequestPriority,
+
+    /// The time that the request was made, which is used to stop requests with a low priority from waiting forever
+    pub queued_at: Instant,
+
+    /// The request isn't sent again until this time, when it is waiting for the retry policy's backoff
+    pub not_before: Option<Instant>,
 }
 
 impl RequestState {
-    /// Commits data to the request state, and then wakes up the task so that the async block can unblock
-    pub fn commit(&mut self, response: Result<hyper::Response<Body>, Error>) {
+    /// Commits data to the request state, and then wakes up the task so that the async block can unblock.
+    /// The response is dropped if the request was cancelled
+    pub fn commit(&mut self, response: Result<hyper::Response<Body>, TransportError>) {
+        if self.cancelled {
+            return;
+        }
         self.response = Some(response);
         if let Some(waker) = self.waker.as_ref() {
             waker.wake_by_ref()
@@ -37,21 +69,85 @@ impl RequestState {
     }
 }
 
+/// The parts of a request that are needed to send it more than once, with the body read into memory
+pub struct RequestParts {
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+impl RequestParts {
+    /// Reads the body of a request into memory
+    pub async fn read(request: Request<Body>) -> Result<Self, TransportError> {
+        let (parts, body) = request.into_parts();
+        Ok(RequestParts {
+            method: parts.method,
+            uri: parts.uri,
+            headers: parts.headers,
+            body: hyper::body::to_bytes(body).await?,
+        })
+    }
+
+    /// Whether sending the request more than once has the same effect as sending it once
+    pub fn is_idempotent(&self) -> bool {
+        self.method.is_idempotent()
+    }
+
+    /// Creates a new request from the parts
+    pub fn to_request(&self) -> Request<Body> {
+        let mut request = Request::new(Body::from(self.body.clone()));
+        *request.method_mut() = self.method.clone();
+        *request.uri_mut() = self.uri.clone();
+        *request.headers_mut() = self.headers.clone();
+        request
+    }
+}
+
 impl HttpFuture {
-    /// Creates a new future with the given request
-    pub fn new(request: Request<Body>) -> Self {
+    /**
+     * Creates a new future with the given request
+     * @param retry_policy Overrides the client's retry policy for this request
+     * @param priority How urgent the request is
+     */
+    pub fn new(
+        request: Request<Body>,
+        retry_policy: Option<RetryPolicy>,
+        priority: RequestPriority,
+    ) -> Self {
         let shared_state = Arc::new(Mutex::new(RequestState {
             request: Some(request),
+            sent: None,
             response: None,
             waker: None,
+            cancelled: false,
+            retry_policy,
+            attempts: 0,
+            priority,
+            queued_at: Instant::now(),
+            not_before: None,
         }));
 
         HttpFuture { shared_state }
     }
+
+    /// Gets the state of the request, so that it can be sent to the http client
+    pub fn state(&self) -> SharedRequest {
+        self.shared_state.clone()
+    }
+}
+
+impl Drop for HttpFuture {
+    fn drop(&mut self) {
+        if let Ok(mut shared_state) = self.shared_state.lock() {
+            shared_state.cancelled = true;
+            shared_state.waker = None;
+        }
+    }
 }
 
 impl Future for HttpFuture {
-    type Output = Result<hyper::Response<Body>, Error>;
+    type Output = Result<hyper::Response<Body>, TransportError>;
 
     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         // Look at the shared state to see if the timer has already completed.
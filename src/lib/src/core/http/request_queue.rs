@@ -1,9 +1,9 @@
 use std::{
     collections::{HashMap, HashSet, LinkedList},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use super::{rate_limit_client::RequestRoute, request_future};
+use super::{rate_limit_client::RequestRoute, request_future::SharedRequest};
 
 /// This is a generic queue that supplies the http client with requests in a given order as designated by the queue.
 pub trait HttpQueue {
@@ -12,7 +12,16 @@ pub trait HttpQueue {
      * @param route The route of the request
      * @param future The request
      */
-    fn push(&mut self, route: &RequestRoute, future: *mut request_future::HttpFuture);
+    fn push(&mut self, route: &RequestRoute, future: SharedRequest);
+    /**
+     * Add a request that was rate limited back to the queue, so that it can be sent again once the rate limit resets.
+     * By default it is added like a new request, but queues should put it back where it was so that requests to a route stay in order
+     * @param route The route of the request
+     * @param future The request
+     */
+    fn requeue(&mut self, route: &RequestRoute, future: SharedRequest) {
+        self.push(route, future);
+    }
     /// Get the requests as sorted by the queue
     fn get_sorted_requests(&self) -> Vec<RequestRoute>;
     /// Get the queue for a given route
@@ -30,7 +39,7 @@ pub struct BucketQueue {
     /// The time that the bucket became empty
     time_of_empty: Instant,
     /// The queue of requests
-    queue: LinkedList<(u64, *mut request_future::HttpFuture)>,
+    queue: LinkedList<(u64, SharedRequest)>,
 }
 
 impl BucketQueue {
@@ -46,17 +55,26 @@ impl BucketQueue {
      * @param time The time that the request was added
      * @param future The request future
      */
-    pub fn push(&mut self, time: u64, future: *mut request_future::HttpFuture) {
+    pub fn push(&mut self, time: u64, future: SharedRequest) {
         self.queue.push_back((time, future));
     }
 
+    /**
+     * Add a request to the front of the queue, so that it is the next one to be sent
+     * @param time The time that the request was added
+     * @param future The request future
+     */
+    pub fn push_front(&mut self, time: u64, future: SharedRequest) {
+        self.queue.push_front((time, future));
+    }
+
     /// Get the oldest request in the queue
-    pub fn get_oldest(&self) -> Option<&(u64, *mut request_future::HttpFuture)> {
+    pub fn get_oldest(&self) -> Option<&(u64, SharedRequest)> {
         self.queue.front()
     }
 
     /// Removes the first request in the queue, and returns the request
-    pub fn pop(&mut self) -> Option<(u64, *mut request_future::HttpFuture)> {
+    pub fn pop(&mut self) -> Option<(u64, SharedRequest)> {
         self.queue.pop_front()
     }
 
@@ -88,7 +106,6 @@ pub struct BasicHttpQueue {
 
     active_requests_set: HashSet<RequestRoute>,
 }
-unsafe impl Send for BasicHttpQueue {}
 
 impl BasicHttpQueue {
     /**
@@ -106,7 +123,7 @@ impl BasicHttpQueue {
 
 impl HttpQueue for BasicHttpQueue {
     /// Add a request to the queue
-    fn push(&mut self, route: &RequestRoute, future: *mut request_future::HttpFuture) {
+    fn push(&mut self, route: &RequestRoute, future: SharedRequest) {
         let queue = self
             .queue_map
             .entry(route.clone())
@@ -117,6 +134,19 @@ impl HttpQueue for BasicHttpQueue {
         self.active_requests_set.insert(route.clone());
     }
 
+    /// Add a rate limited request back to the front of its bucket's queue
+    fn requeue(&mut self, route: &RequestRoute, future: SharedRequest) {
+        let queue = self
+            .queue_map
+            .entry(route.clone())
+            .or_default();
+
+        // It is older than every request still in the queue, so it keeps the place of the oldest one
+        let time = queue.get_oldest().map_or(self.req_id_cnt, |(time, _)| *time);
+        queue.push_front(time, future);
+        self.active_requests_set.insert(route.clone());
+    }
+
     /**
      * Gets the request groups in order by the first item's age. This will prioritize
      * Requests with older requests, but will not mean that all requests will be processed in order
@@ -136,12 +166,10 @@ impl HttpQueue for BasicHttpQueue {
     }
 
     fn clean(&mut self) {
+        let timeout = self.inactive_bucket_timeout;
         self.queue_map.retain(|_, v| {
-            v.is_empty()
-                && (Instant::now()
-                    .duration_since(v.get_time_of_empty())
-                    .as_secs()
-                    > self.inactive_bucket_timeout)
+            !v.is_empty()
+                || Instant::now().duration_since(v.get_time_of_empty()).as_secs() <= timeout
         });
     }
 
@@ -158,3 +186,142 @@ impl HttpQueue for BasicHttpQueue {
         !self.queue_map.iter().any(|v| !v.1.is_empty())
     }
 }
+
+/// How urgent a request is. A `PriorityHttpQueue` sends the requests with the highest priority first, while `BasicHttpQueue` ignores it.
+/// Interaction callbacks are sent with `Interaction` priority unless another priority is set, and other requests use `Normal` by default
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RequestPriority {
+    /// Responses to interactions, which have to be sent within 3 seconds
+    Interaction = 0,
+    /// Requests that a user is waiting for, like sending a message
+    UserFacing = 1,
+    /// Requests that don't have a priority set
+    Normal = 2,
+    /// Requests that can wait, like bulk moderation and maintenance
+    Bulk = 3,
+}
+
+impl Default for RequestPriority {
+    fn default() -> Self {
+        RequestPriority::Normal
+    }
+}
+
+/// A queue that sends the routes with the most urgent requests first, as set with `ctx.with_priority`.
+/// Requests to the same route are still sent in the order they were made, so the priority of a route is the priority of its oldest request.
+/// To stop requests with a low priority from waiting forever, a request moves up one priority for every `aging` that it has waited
+pub struct PriorityHttpQueue {
+    /// Holds the requests. Only the order of the routes is different
+    queue: BasicHttpQueue,
+    /// How long a request waits before it moves up one priority
+    aging: Duration,
+}
+
+impl PriorityHttpQueue {
+    /**
+     * @param inactive_bucket_timeout: the time in seconds after which a bucket is considered inactive, and can be deleted
+     * @param aging: how long a request waits before it moves up one priority. A bulk request is sent like an interaction callback after waiting 3 times this long
+     */
+    pub fn new(inactive_bucket_timeout: u64, aging: Duration) -> PriorityHttpQueue {
+        PriorityHttpQueue {
+            queue: BasicHttpQueue::new(inactive_bucket_timeout),
+            aging,
+        }
+    }
+
+    /// Gets the priority of a request after it has been moved up for the time it has waited. Lower values are sent first
+    fn effective_priority(&self, request: &SharedRequest, now: Instant) -> u128 {
+        let state = request.lock().unwrap();
+        let waited = now.saturating_duration_since(state.queued_at);
+        let promotions = waited.as_millis() / self.aging.as_millis().max(1);
+        (state.priority as u128).saturating_sub(promotions)
+    }
+}
+
+impl HttpQueue for PriorityHttpQueue {
+    fn push(&mut self, route: &RequestRoute, future: SharedRequest) {
+        self.queue.push(route, future);
+    }
+
+    fn requeue(&mut self, route: &RequestRoute, future: SharedRequest) {
+        self.queue.requeue(route, future);
+    }
+
+    /**
+     * Gets the routes in order by the priority of their oldest request, after it has been moved up for the time it has waited.
+     * Routes with the same priority are ordered by the age of their oldest request
+     */
+    fn get_sorted_requests(&self) -> Vec<RequestRoute> {
+        let now = Instant::now();
+        let mut q: Vec<RequestRoute> = self.queue.active_requests_set.iter().cloned().collect();
+
+        q.sort_by_cached_key(|route| {
+            let (time, request) = self.queue.queue_map.get(route).unwrap().get_oldest().unwrap();
+            (self.effective_priority(request, now), *time)
+        });
+        q
+    }
+
+    fn clean(&mut self) {
+        self.queue.clean();
+    }
+
+    fn get_bucket_queue(&mut self, route: &RequestRoute) -> Option<&mut BucketQueue> {
+        self.queue.get_bucket_queue(route)
+    }
+
+    fn notify_empty(&mut self, route: &RequestRoute) {
+        self.queue.notify_empty(route);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use hyper::{Body, Request};
+
+    use crate::core::http::{
+        rate_limit_client::RequestRoute,
+        request_future::{HttpFuture, SharedRequest},
+    };
+
+    use super::{HttpQueue, PriorityHttpQueue, RequestPriority};
+
+    const AGING: Duration = Duration::from_secs(10);
+
+    /// Creates a request that was made the given time ago
+    fn request(priority: RequestPriority, waited: Duration) -> SharedRequest {
+        let state = HttpFuture::new(Request::new(Body::empty()), None, priority).state();
+        state.lock().unwrap().queued_at = Instant::now() - waited;
+        state
+    }
+
+    fn route(channel_id: u64) -> RequestRoute {
+        RequestRoute::from_path(&format!("/channels/{}/messages", channel_id))
+    }
+
+    #[test]
+    fn sends_urgent_requests_first() {
+        let mut queue = PriorityHttpQueue::new(60, AGING);
+        queue.push(&route(1), request(RequestPriority::Bulk, Duration::ZERO));
+        queue.push(&route(2), request(RequestPriority::Interaction, Duration::ZERO));
+
+        assert_eq!(queue.get_sorted_requests(), vec![route(2), route(1)]);
+    }
+
+    #[test]
+    fn aging_promotes_old_requests() {
+        let mut queue = PriorityHttpQueue::new(60, AGING);
+        // Bulk is 3 priorities below Interaction, so it is sent like an interaction callback once it has waited 3 times the aging
+        queue.push(&route(1), request(RequestPriority::Bulk, AGING * 3));
+        queue.push(&route(2), request(RequestPriority::Interaction, Duration::ZERO));
+        queue.push(&route(3), request(RequestPriority::Interaction, Duration::ZERO));
+
+        assert_eq!(queue.get_sorted_requests(), vec![route(1), route(2), route(3)]);
+    }
+}
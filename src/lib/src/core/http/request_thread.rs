@@ -1,63 +1,305 @@
-use std::{collections::HashMap, thread, time::Instant};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
 
-use crossbeam_channel::Receiver;
-use hyper::{client::ResponseFuture, Client};
-use hyper_tls::HttpsConnector;
+use crossbeam_channel::{at, never, select, unbounded, Receiver, Sender};
+use hyper::{Body, HeaderMap, Response, StatusCode};
+use serde::Deserialize;
 
 use crate::util::requests::get_header_as;
 
 use super::{
     rate_limit_client::{RequestObject, RequestRoute},
     request_bucket,
-    request_future::{self},
+    request_future::{RequestParts, SharedRequest},
     request_queue::HttpQueue,
+    retry_policy::RetryPolicy,
+    transport::HttpTransport,
 };
 
 const GLOBAL_RATE_LIMIT_PER_SEC: f64 = 50f64;
 const CLEAN_EVERY_N_REQUESTS: u64 = 10_000;
+/// Buckets that haven't been used for this long are forgotten when the rate limits are cleaned
+const IDLE_BUCKET_TIMEOUT: Duration = Duration::from_secs(300);
+/// How long to wait before retrying a rate limited request if discord doesn't say how long to wait
+const DEFAULT_RETRY_AFTER: f64 = 1f64;
+/// The bucket that is used for routes that haven't received a response with a bucket yet
+const UNKNOWN_BUCKET: &str = "UNKNOWN";
+
+/// Sent from the tasks that send requests back to the request thread
+enum RequestEvent {
+    /// A response was received, so the rate limits can be updated from its headers
+    Response {
+        route: RequestRoute,
+        bucket_name: String,
+        headers: HeaderMap,
+        /// The rate limit that the request ran into, if it received a 429
+        rate_limited: Option<RateLimited>,
+    },
+    /// A batch of requests has finished
+    Finished {
+        route: RequestRoute,
+        /// The request that has to be sent again, because it was rate limited or failed in a way that can be retried
+        retry: Option<SharedRequest>,
+        /// The requests in the batch after the one that is retried, which were never sent. They are given back their allowance
+        unsent: Vec<SharedRequest>,
+        /// Whether a response updated the route's bucket, which means that the unsent requests are no longer counted against it
+        responded: bool,
+    },
+}
+
+/// The body of a 429 response
+/// @docs <https://discord.com/developers/docs/topics/rate-limits#exceeding-a-rate-limit-rate-limit-response-structure>
+#[derive(Deserialize)]
+struct RateLimitResponse {
+    /// The number of seconds to wait before submitting another request
+    retry_after: f64,
+    /// Whether this is a global rate limit
+    #[serde(default)]
+    global: bool,
+}
+
+/// A rate limit that a request ran into
+struct RateLimited {
+    /// How long to wait before sending another request
+    retry_after: Duration,
+    /// Whether every request is limited, rather than just the requests in the bucket
+    global: bool,
+}
+
+impl RateLimited {
+    /// Reads how long to wait from a 429 response. The body is used if it can be read, otherwise the headers are used
+    async fn from_response(response: Response<Body>) -> Self {
+        let headers = response.headers().clone();
+        let body = hyper::body::to_bytes(response.into_body())
+            .await
+            .ok()
+            .and_then(|body| serde_json::from_slice::<RateLimitResponse>(&body).ok());
+
+        let (retry_after, global) = match body {
+            Some(body) => (body.retry_after, body.global),
+            None => (
+                get_header_as::<f64>(&headers, "Retry-After").unwrap_or(DEFAULT_RETRY_AFTER),
+                false,
+            ),
+        };
+        let global = global
+            || get_header_as::<bool>(&headers, "X-RateLimit-Global").unwrap_or(false)
+            || get_header_as::<String>(&headers, "X-RateLimit-Scope").as_deref() == Some("global");
+
+        RateLimited {
+            retry_after: Duration::from_secs_f64(retry_after.max(0f64)),
+            global,
+        }
+    }
+}
+
+/// Gets the time that a bucket resets from the `X-RateLimit-Reset-After` header, which is more precise than `X-RateLimit-Reset`
+fn get_reset_at(headers: &HeaderMap) -> Instant {
+    let reset_after = get_header_as::<f64>(headers, "X-RateLimit-Reset-After").unwrap_or(0f64);
+    Instant::now() + Duration::from_secs_f64(reset_after.max(0f64))
+}
+
+/// The rate limits that discord has sent back, which decide when requests can be sent
+struct RateLimits {
+    /// The buckets by their key, which is the bucket hash from discord and the major parameter of the route, as `{hash}:{major_param}`
+    rate_buckets: HashMap<String, request_bucket::Bucket>,
+    /// The key of the bucket for each route that has received a response with a bucket hash
+    route_to_bucket: HashMap<RequestRoute, String>,
+    /// No requests can be sent until this time if the global rate limit was hit
+    global_reset_at: Instant,
+}
+
+impl RateLimits {
+    fn new() -> Self {
+        let mut rate_buckets = HashMap::new();
+        rate_buckets.insert(UNKNOWN_BUCKET.to_string(), request_bucket::Bucket::new());
+        RateLimits {
+            rate_buckets,
+            route_to_bucket: HashMap::new(),
+            global_reset_at: Instant::now(),
+        }
+    }
+
+    /// Gets the name of the bucket for a route, and the bucket itself
+    fn get_bucket(&mut self, route: &RequestRoute) -> (String, &mut request_bucket::Bucket) {
+        let bucket_name = self
+            .route_to_bucket
+            .get(route)
+            .map_or(UNKNOWN_BUCKET, |bucket| bucket.as_str())
+            .to_string();
+        let bucket = self.rate_buckets.get_mut(&bucket_name).unwrap();
+        (bucket_name, bucket)
+    }
+
+    /// Updates the bucket that a request was sent in with the rate limit headers of its response
+    fn update(&mut self, route: &RequestRoute, bucket_name: &str, headers: &HeaderMap) {
+        let remaining_requests =
+            get_header_as::<i32>(headers, "X-RateLimit-Remaining").unwrap_or(0);
+        let max_requests = get_header_as::<i32>(headers, "X-RateLimit-Limit").unwrap_or(1);
+        let reset_at = get_reset_at(headers);
+
+        let bucket = if bucket_name == UNKNOWN_BUCKET {
+            // Routes with the same hash only share a bucket if they also have the same major parameter
+            // @docs <https://discord.com/developers/docs/topics/rate-limits#header-format>
+            let bucket_name = get_header_as::<String>(headers, "X-RateLimit-Bucket")
+                .map(|hash| format!("{}:{}", hash, route.major_param));
+
+            self.rate_buckets
+                .get_mut(UNKNOWN_BUCKET)
+                .unwrap()
+                .remaining_requests = 1;
+            if let Some(bucket_name) = bucket_name {
+                self.route_to_bucket
+                    .insert(route.clone(), bucket_name.clone());
+                Some(self.rate_buckets.entry(bucket_name).or_insert_with(|| {
+                    request_bucket::Bucket {
+                        max_requests,
+                        remaining_requests,
+                        reset_at,
+                    }
+                }))
+            } else {
+                None
+            }
+        } else {
+            self.rate_buckets.get_mut(bucket_name)
+        };
+        if let Some(bucket) = bucket {
+            bucket.remaining_requests = remaining_requests;
+            bucket.max_requests = max_requests;
+            bucket.reset_at = reset_at;
+        }
+    }
+
+    /**
+     * Forgets the buckets that reset a while ago, and the routes that use them, so that the maps don't keep growing.
+     * Their routes go back to the unknown bucket until they get a response with a bucket again
+     * @param in_use The routes that have requests being sent, whose buckets are kept
+     */
+    fn clean(&mut self, in_use: &HashSet<RequestRoute>) {
+        let now = Instant::now();
+        let kept: HashSet<String> = in_use
+            .iter()
+            .filter_map(|route| self.route_to_bucket.get(route).cloned())
+            .collect();
+        self.rate_buckets.retain(|name, bucket| {
+            name == UNKNOWN_BUCKET
+                || kept.contains(name)
+                || now.saturating_duration_since(bucket.reset_at) < IDLE_BUCKET_TIMEOUT
+        });
+        let rate_buckets = &self.rate_buckets;
+        self.route_to_bucket
+            .retain(|_, bucket_name| rate_buckets.contains_key(bucket_name));
+    }
+
+    /// Pauses the bucket of a route (or every bucket) until a rate limit that it ran into resets
+    fn limit(&mut self, route: &RequestRoute, limit: RateLimited) {
+        let reset_at = Instant::now() + limit.retry_after;
+        if limit.global {
+            self.global_reset_at = self.global_reset_at.max(reset_at);
+        } else {
+            let (_, bucket) = self.get_bucket(route);
+            bucket.remaining_requests = 0;
+            bucket.reset_at = bucket.reset_at.max(reset_at);
+        }
+    }
+}
 
 /**
  * Creates the request thread that will batch requests out according to rate limit headers that are returned by discord, and also the
- * Global rate limit of GLOBAL_RATE_LIMIT_PER_SEC
+ * Global rate limit of GLOBAL_RATE_LIMIT_PER_SEC.
+ * Requests are sent on an async runtime, so that routes are sent concurrently, while the requests for each route are sent one after another, in order.
  * @param send_queue The Shared Queue that requests can be added to
+ * @param in_progress Counts the requests that have been sent to the thread but not finished. They are counted by the sender. The thread stops once every sender is dropped
+ * @param retry_policy The retry policy for requests that don't have their own
+ * @param transport Sends the requests
  */
-pub fn create_thread<T>(mut http_queue: T, receiver: Receiver<RequestObject>)
-where
+pub fn create_thread<T>(
+    mut http_queue: T,
+    receiver: Receiver<RequestObject>,
+    in_progress: Arc<AtomicUsize>,
+    retry_policy: Arc<RwLock<RetryPolicy>>,
+    transport: Arc<dyn HttpTransport>,
+) where
     T: HttpQueue + Send + 'static,
 {
     thread::Builder::new()
         .name("Request_Thread".to_string())
         .spawn(move || {
-            let https = HttpsConnector::new();
-            let client = Client::builder().build::<_, hyper::Body>(https);
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .thread_name("Request_Worker")
+                .enable_all()
+                .build()
+                .unwrap();
 
             let mut global_allowance: f64 = GLOBAL_RATE_LIMIT_PER_SEC;
             let mut last_timestamp = Instant::now();
             let mut requests_sent: u64 = 0;
 
-            // TODO: Clean the buckets at certain times, also clean the send_queue so that the hashmap doesn't continuously grow in size
-            let mut rate_buckets: HashMap<String, request_bucket::Bucket> = HashMap::new();
-            let mut route_to_bucket: HashMap<RequestRoute, String> = HashMap::new();
+            let mut rate_limits = RateLimits::new();
+            // The routes that have a batch of requests being sent. Only one batch is sent for a route at a time, so that its requests stay in order
+            let mut in_flight: HashSet<RequestRoute> = HashSet::new();
+            let (event_sender, events) = unbounded::<RequestEvent>();
+
+            let push = |http_queue: &mut T, obj: RequestObject| {
+                http_queue.push(&obj.route, obj.future);
+            };
 
-            rate_buckets.insert("UNKNOWN".to_string(), request_bucket::Bucket::new());
+            // Set once every sender has been dropped. The requests that are left still have to be sent
+            let mut disconnected = false;
+            let closed = never::<RequestObject>();
+            // The earliest time that a queued request can be sent, if the queue is waiting on a rate limit
+            let mut next_wake: Option<Instant> = None;
 
             // Main Request Loop
             loop {
-                if http_queue.is_empty() {
-                    let obj = receiver.recv().unwrap();
-                    http_queue.push(&obj.route, obj.future);
+                // Wait until there is a new request, a response, or a rate limit that a request is waiting on has reset
+                if http_queue.is_empty() && in_flight.is_empty() {
+                    if disconnected {
+                        return;
+                    }
+                    match receiver.recv() {
+                        Ok(obj) => push(&mut http_queue, obj),
+                        // Every sender has been dropped, so there will never be another request
+                        Err(_) => return,
+                    };
+                } else {
+                    let requests = if disconnected { &closed } else { &receiver };
+                    let wake = next_wake.map_or_else(never, at);
+                    select! {
+                        recv(requests) -> obj => match obj {
+                            Ok(obj) => push(&mut http_queue, obj),
+                            Err(_) => disconnected = true,
+                        },
+                        recv(events) -> event => if let Ok(event) = event {
+                            handle_event(&mut http_queue, &mut rate_limits, &mut in_flight, &mut global_allowance, event);
+                        },
+                        recv(wake) -> _ => {},
+                    }
                 }
+                next_wake = None;
                 // Add incoming requests to the queue
-                while !receiver.is_empty() {
-                    let obj = receiver.recv().unwrap();
-                    http_queue.push(&obj.route, obj.future);
+                while let Ok(obj) = receiver.try_recv() {
+                    push(&mut http_queue, obj);
+                }
+                // Update the rate limits with the responses that have been received
+                while let Ok(event) = events.try_recv() {
+                    handle_event(&mut http_queue, &mut rate_limits, &mut in_flight, &mut global_allowance, event);
                 }
 
-                // TODO Figure out a smarter way to do this
-                // // check if we should clean the queue, and the buckets
-                // if requests_sent % CLEAN_EVERY_N_REQUESTS == 0 {
-                //     http_queue.clean();
-                // }
+                // Forget the routes and buckets that haven't been used in a while
+                if requests_sent >= CLEAN_EVERY_N_REQUESTS {
+                    requests_sent = 0;
+                    http_queue.clean();
+                    rate_limits.clean(&in_flight);
+                }
 
                 // Add more allowance to the global limit
                 let temp_time = Instant::now();
@@ -66,53 +308,56 @@ where
 
                 if global_allowance > GLOBAL_RATE_LIMIT_PER_SEC {
                     global_allowance = GLOBAL_RATE_LIMIT_PER_SEC;
-                    thread::yield_now();
                 }
 
                 last_timestamp = Instant::now();
 
-                let sorted_routes = http_queue.get_sorted_requests();
+                // Nothing can be sent until the global rate limit resets
+                if rate_limits.global_reset_at > Instant::now() {
+                    next_wake = Some(rate_limits.global_reset_at);
+                    continue;
+                }
 
-                let mut responses: Vec<(
-                    RequestRoute,
-                    &mut request_future::HttpFuture,
-                    ResponseFuture,
-                    String,
-                )> = Vec::new();
+                let sorted_routes = http_queue.get_sorted_requests();
 
-                // Iterate through all of the requests in the queue, and add them to the futures vector if they can be executed
+                // Iterate through all of the requests in the queue, and send a batch for each route that can send requests
                 for route in sorted_routes {
-                    // Get the bucket for this route, or create it if it doesn't exist
-                    let bucket = match route_to_bucket.get(&route) {
-                        None => (
-                            "UNKNOWN".to_string(),
-                            rate_buckets.get_mut("UNKNOWN").unwrap(),
-                        ),
-                        Some(bucket) => (bucket.to_string(), rate_buckets.get_mut(bucket).unwrap()),
-                    };
+                    if in_flight.contains(&route) {
+                        continue;
+                    }
+                    // Get the bucket for this route
+                    let (bucket_name, bucket) = rate_limits.get_bucket(&route);
 
                     // Reset the bucket if it is past the reset time
-                    if bucket.1.reset_at < chrono::Utc::now().timestamp() {
-                        bucket.1.remaining_requests = bucket.1.max_requests;
+                    if bucket.reset_at <= Instant::now() {
+                        bucket.remaining_requests = bucket.max_requests;
                     }
 
                     // get the queue for the route, and then get as many requests as possible from the queue
                     // This means it will take min(global_limit, bucket.remaining_requests) requests from the queue
                     let queue = http_queue.get_bucket_queue(&route).unwrap();
-                    while bucket.1.remaining_requests > 0 && global_allowance >= 1f64 {
-                        // Pop the front and add it to the futures vector if it exists, or break out if the queue is empty
+                    // A request that is waiting to be retried holds up the requests behind it, so that they stay in order
+                    let retry_at = queue
+                        .get_oldest()
+                        .and_then(|(_, request)| request.lock().unwrap().not_before)
+                        .filter(|retry_at| *retry_at > Instant::now());
+                    if let Some(retry_at) = retry_at {
+                        next_wake = Some(next_wake.map_or(retry_at, |at| at.min(retry_at)));
+                        continue;
+                    }
+                    let mut batch = Vec::new();
+                    while bucket.remaining_requests > 0 && global_allowance >= 1f64 {
+                        // Pop the front and add it to the batch if it exists, or break out if the queue is empty
                         match queue.pop() {
-                            Some((_, req_future)) => {
-                                let future_ptr = unsafe { &mut *req_future };
-
-                                let req = {
-                                    let mut shared_state = future_ptr.shared_state.lock().unwrap();
-                                    client.request(shared_state.request.take().unwrap())
-                                };
-                                responses.push((route.clone(), future_ptr, req, bucket.0.clone()));
+                            // Requests that were cancelled while they were queued are dropped without being sent
+                            Some((_, request)) if request.lock().unwrap().cancelled => {
+                                in_progress.fetch_sub(1, Ordering::SeqCst);
+                            }
+                            Some((_, request)) => {
+                                batch.push(request);
                                 requests_sent += 1;
 
-                                bucket.1.remaining_requests -= 1;
+                                bucket.remaining_requests -= 1;
                                 global_allowance -= 1f64;
                             }
                             None => {
@@ -122,87 +367,280 @@ where
                     }
                     if queue.is_empty() {
                         http_queue.notify_empty(&route);
+                    } else if batch.is_empty() && global_allowance >= 1f64 {
+                        // The route is waiting for its bucket to reset
+                        next_wake = Some(next_wake.map_or(bucket.reset_at, |at| at.min(bucket.reset_at)));
+                    }
+                    if !batch.is_empty() {
+                        in_flight.insert(route.clone());
+                        runtime.spawn(send_batch(
+                            transport.clone(),
+                            route,
+                            bucket_name,
+                            batch,
+                            retry_policy.read().unwrap().clone(),
+                            event_sender.clone(),
+                            in_progress.clone(),
+                        ));
                     }
                     if global_allowance < 1f64 {
+                        // Every route has to wait for the global allowance to refill
+                        let refill_at = Instant::now()
+                            + Duration::from_secs_f64(
+                                (1f64 - global_allowance) / GLOBAL_RATE_LIMIT_PER_SEC,
+                            );
+                        next_wake = Some(next_wake.map_or(refill_at, |at| at.min(refill_at)));
                         break;
                     }
                 }
+            }
+        })
+        .unwrap();
+}
 
-                // Convert the requests into a vector of response futures by having the hyper client make them
-
-                let mut last_date_map: HashMap<RequestRoute, i64> = HashMap::new();
-
-                // Collect the responses, and resolve all of the Request Futures
-                for (route, req, future, bucket_name) in responses {
-                    // Block execution until the future is resolved, and then process the rate limit information from the response
-                    // TODO figure out how to make this run in parallel
-                    let receives = match async_std::task::block_on(future) {
-                        Ok(received) => {
-                            // Get the date of the response execution so that we know the last time the route was used,
-                            // And therefore the most up to date rate limit information for each route
-                            let date_raw = received.headers().get("Date").unwrap().as_bytes();
-                            let date = chrono::DateTime::parse_from_rfc2822(
-                                std::str::from_utf8(date_raw).unwrap(),
-                            )
-                            .unwrap()
-                            .timestamp();
-
-                            // Only update rate limit information if this request is more recent than the rest
-                            if date > *last_date_map.get(&route).or(Some(&0)).unwrap() {
-                                last_date_map.insert(route.clone(), date);
-
-                                let remaining_requests = get_header_as::<i32>(
-                                    received.headers(),
-                                    "X-RateLimit-Remaining",
-                                )
-                                .unwrap_or(0);
-
-                                let max_requests =
-                                    get_header_as::<i32>(received.headers(), "X-RateLimit-Limit")
-                                        .unwrap_or(1);
-
-                                let reset_at =
-                                    get_header_as::<i64>(received.headers(), "X-RateLimit-Reset")
-                                        .unwrap_or(0); // TODO make this an actual value
-
-                                let bucket = if bucket_name == "UNKNOWN" {
-                                    let bucket_name = get_header_as::<String>(
-                                        received.headers(),
-                                        "X-RateLimit-Bucket",
-                                    );
-
-                                    rate_buckets.get_mut("UNKNOWN").unwrap().remaining_requests = 1;
-                                    if let Some(bucket_name) = bucket_name {
-                                        route_to_bucket
-                                            .insert(route.clone(), bucket_name.to_string());
-                                        Some(rate_buckets.entry(bucket_name).or_insert_with(|| {
-                                            request_bucket::Bucket {
-                                                max_requests,
-                                                remaining_requests,
-                                                reset_at,
-                                            }
-                                        }))
-                                    } else {
-                                        None
-                                    }
-                                } else {
-                                    rate_buckets.get_mut(&bucket_name)
-                                };
-                                if let Some(bucket) = bucket {
-                                    bucket.remaining_requests = remaining_requests;
-                                    bucket.max_requests = max_requests;
-                                    bucket.reset_at = reset_at;
-                                }
-                            }
-                            Ok(received)
-                        }
-                        Err(e) => Err(e),
-                    };
+/// Updates the rate limits with an event from a batch, and puts back the requests that weren't sent
+fn handle_event<T: HttpQueue>(
+    http_queue: &mut T,
+    rate_limits: &mut RateLimits,
+    in_flight: &mut HashSet<RequestRoute>,
+    global_allowance: &mut f64,
+    event: RequestEvent,
+) {
+    match event {
+        RequestEvent::Response {
+            route,
+            bucket_name,
+            headers,
+            rate_limited,
+        } => {
+            rate_limits.update(&route, &bucket_name, &headers);
+            if let Some(limit) = rate_limited {
+                rate_limits.limit(&route, limit);
+            }
+        }
+        RequestEvent::Finished {
+            route,
+            retry,
+            unsent,
+            responded,
+        } => {
+            // The unsent requests were counted when the batch was made, so their allowance is given back
+            let refund = unsent.len() as i32;
+            *global_allowance = (*global_allowance + refund as f64).min(GLOBAL_RATE_LIMIT_PER_SEC);
+            if !responded {
+                let (_, bucket) = rate_limits.get_bucket(&route);
+                bucket.remaining_requests = (bucket.remaining_requests + refund).min(bucket.max_requests);
+            }
+
+            // Each request is put at the front of the queue, so they are added back to front to keep them in order
+            for future in unsent.into_iter().rev().chain(retry) {
+                http_queue.requeue(&route, future);
+            }
+            in_flight.remove(&route);
+        }
+    }
+}
+
+/**
+ * Sends a batch of requests for a route one after another, and resolves their futures.
+ * If a request is rate limited, it and the rest of the batch are sent back to the request thread to be sent again once the rate limit resets.
+ * If a request fails in a way that the retry policy allows to be retried, the same is done, and the request isn't sent again until the policy's backoff has passed
+ */
+async fn send_batch(
+    transport: Arc<dyn HttpTransport>,
+    route: RequestRoute,
+    bucket_name: String,
+    batch: Vec<SharedRequest>,
+    retry_policy: RetryPolicy,
+    events: Sender<RequestEvent>,
+    in_progress: Arc<AtomicUsize>,
+) {
+    let mut batch = batch.into_iter();
+    // Whether the bucket has been updated from a response, which stops counting the requests that are left in the batch against it
+    let mut responded = false;
+    while let Some(future) = batch.next() {
+        // The body is read into memory the first time the request is sent, so that it can be sent again if it is rate limited
+        let request = {
+            let mut shared_state = future.lock().unwrap();
+            // The caller stopped waiting for the response while the request was waiting for the ones before it
+            if shared_state.cancelled {
+                in_progress.fetch_sub(1, Ordering::SeqCst);
+                continue;
+            }
+            shared_state.request.take()
+        };
+        if let Some(request) = request {
+            let parts = RequestParts::read(request).await;
+            let mut shared_state = future.lock().unwrap();
+            match parts {
+                Ok(parts) => shared_state.sent = Some(parts),
+                Err(e) => {
+                    shared_state.commit(Err(e));
+                    in_progress.fetch_sub(1, Ordering::SeqCst);
+                    continue;
+                }
+            }
+        }
+        let request = future
+            .lock()
+            .unwrap()
+            .sent
+            .as_ref()
+            .unwrap()
+            .to_request();
 
-                    let mut shared_state = req.shared_state.lock().unwrap();
-                    shared_state.commit(receives);
+        let received = match transport.send(request).await {
+            Ok(received) => {
+                // Pause the bucket (or every bucket) until the rate limit resets, and then send the request again
+                if received.status() == StatusCode::TOO_MANY_REQUESTS {
+                    let headers = received.headers().clone();
+                    let rate_limited = RateLimited::from_response(received).await;
+                    events
+                        .send(RequestEvent::Response {
+                            route: route.clone(),
+                            bucket_name,
+                            headers,
+                            rate_limited: Some(rate_limited),
+                        })
+                        .ok();
+                    events
+                        .send(RequestEvent::Finished {
+                            route,
+                            retry: Some(future),
+                            unsent: batch.collect(),
+                            responded: true,
+                        })
+                        .ok();
+                    return;
                 }
+                responded = true;
+                events
+                    .send(RequestEvent::Response {
+                        route: route.clone(),
+                        bucket_name: bucket_name.clone(),
+                        headers: received.headers().clone(),
+                        rate_limited: None,
+                    })
+                    .ok();
+                Ok(received)
             }
+            Err(e) => Err(e),
+        };
+
+        let retry_delay = {
+            let mut shared_state = future.lock().unwrap();
+            shared_state.attempts += 1;
+            let idempotent = shared_state.sent.as_ref().unwrap().is_idempotent();
+            shared_state
+                .retry_policy
+                .as_ref()
+                .unwrap_or(&retry_policy)
+                .retry_delay(shared_state.attempts, idempotent, &received)
+        };
+        // The request is queued again straight away, so that the route isn't held up while it waits for the backoff.
+        // The rest of the batch is queued behind it so that they stay in order
+        if let Some(retry_delay) = retry_delay {
+            future.lock().unwrap().not_before = Some(Instant::now() + retry_delay);
+            events
+                .send(RequestEvent::Finished {
+                    route,
+                    retry: Some(future),
+                    unsent: batch.collect(),
+                    responded,
+                })
+                .ok();
+            return;
+        }
+
+        // If the request was cancelled while it was being sent, nothing is waiting for the response so it is dropped
+        let mut shared_state = future.lock().unwrap();
+        shared_state.commit(received);
+        in_progress.fetch_sub(1, Ordering::SeqCst);
+    }
+    events
+        .send(RequestEvent::Finished {
+            route,
+            retry: None,
+            unsent: Vec::new(),
+            responded,
         })
-        .unwrap();
+        .ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::HeaderMap;
+
+    use crate::core::http::rate_limit_client::RequestRoute;
+
+    use std::{
+        collections::HashSet,
+        time::{Duration, Instant},
+    };
+
+    use super::{RateLimits, IDLE_BUCKET_TIMEOUT, UNKNOWN_BUCKET};
+
+    /// The rate limit headers of a response in the bucket with the given hash
+    fn headers(hash: &str, remaining: i32) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-RateLimit-Bucket", hash.parse().unwrap());
+        headers.insert("X-RateLimit-Limit", "5".parse().unwrap());
+        headers.insert("X-RateLimit-Remaining", remaining.to_string().parse().unwrap());
+        headers.insert("X-RateLimit-Reset-After", "1.5".parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn separates_buckets_by_major_parameter() {
+        let mut rate_limits = RateLimits::new();
+        let first = RequestRoute::from_path("/channels/1/messages");
+        let second = RequestRoute::from_path("/channels/2/messages");
+        rate_limits.update(&first, UNKNOWN_BUCKET, &headers("abcd", 0));
+        rate_limits.update(&second, UNKNOWN_BUCKET, &headers("abcd", 4));
+
+        let (first_name, first_bucket) = rate_limits.get_bucket(&first);
+        assert_eq!(first_name, "abcd:1");
+        assert_eq!(first_bucket.remaining_requests, 0);
+        let (second_name, second_bucket) = rate_limits.get_bucket(&second);
+        assert_eq!(second_name, "abcd:2");
+        assert_eq!(second_bucket.remaining_requests, 4);
+    }
+
+    #[test]
+    fn shares_buckets_with_the_same_hash_and_major_parameter() {
+        let mut rate_limits = RateLimits::new();
+        let messages = RequestRoute::from_path("/channels/1/messages");
+        let message = RequestRoute::from_path("/channels/1/messages/2");
+        rate_limits.update(&messages, UNKNOWN_BUCKET, &headers("abcd", 4));
+        rate_limits.update(&message, UNKNOWN_BUCKET, &headers("abcd", 3));
+
+        let (messages_name, _) = rate_limits.get_bucket(&messages);
+        let (message_name, bucket) = rate_limits.get_bucket(&message);
+        assert_eq!(messages_name, message_name);
+        assert_eq!(bucket.remaining_requests, 3);
+        assert_eq!(bucket.max_requests, 5);
+    }
+
+    #[test]
+    fn forgets_idle_buckets() {
+        let mut rate_limits = RateLimits::new();
+        let idle = RequestRoute::from_path("/channels/1/messages");
+        let active = RequestRoute::from_path("/channels/2/messages");
+        let sending = RequestRoute::from_path("/channels/3/messages");
+        for route in [&idle, &active, &sending] {
+            rate_limits.update(route, UNKNOWN_BUCKET, &headers("abcd", 4));
+        }
+        let long_ago = Instant::now() - IDLE_BUCKET_TIMEOUT - Duration::from_secs(1);
+        rate_limits.get_bucket(&idle).1.reset_at = long_ago;
+        rate_limits.get_bucket(&sending).1.reset_at = long_ago;
+
+        let mut in_flight = HashSet::new();
+        in_flight.insert(sending.clone());
+        rate_limits.clean(&in_flight);
+
+        assert_eq!(rate_limits.get_bucket(&idle).0, UNKNOWN_BUCKET);
+        assert_eq!(rate_limits.get_bucket(&active).0, "abcd:2");
+        assert_eq!(rate_limits.get_bucket(&sending).0, "abcd:3");
+        assert_eq!(rate_limits.rate_buckets.len(), 3);
+    }
 }
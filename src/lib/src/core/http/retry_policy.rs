@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use hyper::{Body, Response};
+use rand::Rng;
+
+use super::transport::TransportError;
+
+/// Decides which failed requests are sent again, and how long to wait before each attempt.
+/// Every attempt is queued again in its bucket, so retries still respect the rate limits.
+/// Rate limited (429) responses are always sent again, and don't count as attempts
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// The most times that a request is sent, including the first attempt. 1 disables retries
+    pub max_attempts: u32,
+    /// How long to wait before the first retry. It doubles after each attempt
+    pub base_delay: Duration,
+    /// The longest time to wait between attempts
+    pub max_delay: Duration,
+    /// The largest fraction (between 0 and 1) of the delay that is randomly taken off, so that failed requests aren't all retried at the same time
+    pub jitter: f64,
+    /// The response status codes that are retried
+    pub retry_statuses: Vec<u16>,
+    /// Whether requests that fail because of a connection error are retried
+    pub retry_connection_errors: bool,
+    /// Whether requests that aren't idempotent (such as POST and PATCH) are retried if they might have reached discord.
+    /// Doing so can make the action happen twice, like sending a message twice. Requests that failed to connect are always safe to retry
+    pub retry_non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            jitter: 0.5,
+            retry_statuses: vec![500, 502, 503, 504],
+            retry_connection_errors: true,
+            retry_non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries a request
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Gets how long to wait before the next attempt, after the given number of attempts have been made
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+            .min(self.max_delay);
+        delay.mul_f64(1f64 - self.jitter.clamp(0f64, 1f64) * rand::thread_rng().gen::<f64>())
+    }
+
+    /**
+     * Decides whether a request is sent again
+     * @param attempts The number of times that the request has been sent
+     * @param idempotent Whether sending the request more than once has the same effect as sending it once
+     * @param response The result of the last attempt
+     * @return How long to wait before sending it again, or None if it shouldn't be retried
+     */
+    pub(crate) fn retry_delay(
+        &self,
+        attempts: u32,
+        idempotent: bool,
+        response: &Result<Response<Body>, TransportError>,
+    ) -> Option<Duration> {
+        if attempts >= self.max_attempts {
+            return None;
+        }
+        let retryable = match response {
+            Ok(response) => {
+                self.retry_statuses.contains(&response.status().as_u16())
+                    && (idempotent || self.retry_non_idempotent)
+            }
+            // The request never reached discord, so it can always be sent again
+            Err(e) if e.is_connect() => self.retry_connection_errors,
+            Err(_) => self.retry_connection_errors && (idempotent || self.retry_non_idempotent),
+        };
+        retryable.then(|| self.backoff(attempts))
+    }
+}
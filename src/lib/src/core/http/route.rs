@@ -0,0 +1,328 @@
+use hyper::{http::request::Builder, Method, Request};
+
+use crate::{core::abstraction::context::Context, discord::snowflake::Snowflake};
+
+use super::rate_limit_client::RequestRoute;
+
+/**
+Generates the `Route` enum from a list of endpoints. Each endpoint is written as
+
+`Name { field: Type, ... } => METHOD "/path/{field}", major(field, ...);`
+
+The fields are formatted into the path where their name appears, and every field has to be used in the path.
+The `major(...)` part is optional, and lists the fields that are major parameters.
+Fields are named the way that `RequestRoute::from_path` names the parts of a path, so that requests made with a path share buckets with the ones made with a `Route`.
+Discord gives requests with different major parameters (channel_id, guild_id, or webhook_id and token) separate rate limits,
+so they are added to the bucket key, while the other parameters are left as their `{name}` in the key so that those requests share a bucket.
+
+For each endpoint, this generates:
+* A variant of `Route` with the endpoint's fields
+* A branch of `Route::method` which returns the endpoint's HTTP method
+* A branch of `Route::path` which returns the path with the fields filled in
+* A branch of `Route::bucket` which returns the `RequestRoute` that is used to rate limit the request
+*/
+macro_rules! routes {
+    // Joins the major parameters with a `/`
+    (@major) => {
+        String::new()
+    };
+    (@major $first:ident $(, $rest:ident)*) => {{
+        let major = $first.to_string();
+        $(
+            let major = format!("{}/{}", major, $rest);
+        )*
+        major
+    }};
+    (
+        $(
+            $(#[$inner:meta])*
+            $Name:ident { $($field:ident: $Type:ty),* } => $Method:ident $path:literal $(, major($($major:ident),+))?;
+        )+
+    ) => {
+        /// An endpoint of the discord api, with the parameters that are needed to make a request to it
+        #[derive(Clone, Debug, PartialEq, Eq)]
+        pub enum Route {
+            $(
+                $(#[$inner])*
+                $Name { $($field: $Type),* },
+            )+
+        }
+
+        impl Route {
+            /// Gets the HTTP method of the endpoint
+            pub fn method(&self) -> Method {
+                match self {
+                    $(
+                        Route::$Name { .. } => Method::$Method,
+                    )+
+                }
+            }
+
+            /// Gets the path of the endpoint with its parameters filled in, without the base url
+            #[allow(unused_variables)]
+            pub fn path(&self) -> String {
+                match self {
+                    $(
+                        Route::$Name { $($field),* } => format!($path, $($field = $field),*),
+                    )+
+                }
+            }
+
+            /// Gets the key that requests to the endpoint are rate limited by. It is the path with only the major parameters filled in
+            #[allow(unused_variables)]
+            pub fn bucket(&self) -> RequestRoute {
+                match self {
+                    $(
+                        Route::$Name { $($field),* } => RequestRoute {
+                            base_route: $path.to_string(),
+                            major_param: routes!(@major $($($major),+)?),
+                        },
+                    )+
+                }
+            }
+        }
+    };
+}
+
+routes! {
+    /// @docs <https://discord.com/developers/docs/topics/gateway#get-gateway-bot>
+    GetGatewayBot {} => GET "/gateway/bot";
+
+    /// @docs <https://discord.com/developers/docs/resources/user#get-user>
+    GetUser { user_id: String } => GET "/users/{user_id}";
+
+    /// @docs <https://discord.com/developers/docs/topics/oauth2#get-current-bot-application-information>
+    GetCurrentApplication {} => GET "/oauth2/applications/@me";
+
+    /// @docs <https://discord.com/developers/docs/resources/channel#create-message>
+    CreateMessage { channel_id: String } => POST "/channels/{channel_id}/messages", major(channel_id);
+
+    /// @docs <https://discord.com/developers/docs/interactions/application-commands#get-global-application-commands>
+    GetGlobalCommands { application_id: Snowflake } => GET "/applications/{application_id}/commands";
+
+    /// @docs <https://discord.com/developers/docs/interactions/application-commands#get-global-application-command>
+    GetGlobalCommand { application_id: Snowflake, command_id: Snowflake } => GET "/applications/{application_id}/commands/{command_id}";
+
+    /// @docs <https://discord.com/developers/docs/interactions/application-commands#create-global-application-command>
+    CreateGlobalCommand { application_id: Snowflake } => POST "/applications/{application_id}/commands";
+
+    /// @docs <https://discord.com/developers/docs/interactions/application-commands#edit-global-application-command>
+    EditGlobalCommand { application_id: Snowflake, command_id: Snowflake } => PATCH "/applications/{application_id}/commands/{command_id}";
+
+    /// @docs <https://discord.com/developers/docs/interactions/receiving-and-responding#create-interaction-response>
+    CreateInteractionResponse { interaction_id: Snowflake, interaction_token: String } => POST "/interactions/{interaction_id}/{interaction_token}/callback", major(interaction_id);
+
+    /// The interaction's webhook has the application's id and the interaction's token
+    /// @docs <https://discord.com/developers/docs/interactions/receiving-and-responding#edit-original-interaction-response>
+    EditOriginalInteractionResponse { webhook_id: Snowflake, webhook_token: String } => PATCH "/webhooks/{webhook_id}/{webhook_token}/messages/@original", major(webhook_id, webhook_token);
+
+    /// The interaction's webhook has the application's id and the interaction's token
+    /// @docs <https://discord.com/developers/docs/interactions/receiving-and-responding#create-followup-message>
+    CreateFollowupMessage { webhook_id: Snowflake, webhook_token: String } => POST "/webhooks/{webhook_id}/{webhook_token}", major(webhook_id, webhook_token);
+
+    /// @docs <https://discord.com/developers/docs/resources/webhook#execute-webhook>
+    ExecuteWebhook { webhook_id: Snowflake, webhook_token: String } => POST "/webhooks/{webhook_id}/{webhook_token}", major(webhook_id, webhook_token);
+}
+
+impl Route {
+    /// Gets the full url of the endpoint, using the base url in the context's settings
+    pub fn url(&self, ctx: &Context) -> String {
+        format!("{}{}", ctx.settings.get_base_url(), self.path())
+    }
+
+    /// Starts building a request to the endpoint, with its method and url already set
+    pub fn request(&self, ctx: &Context) -> Builder {
+        Request::builder().method(self.method()).uri(self.url(ctx))
+    }
+}
+
+/// The resources whose id is a major parameter, when it comes right after them at the start of the path
+const MAJOR_RESOURCES: [&str; 4] = ["channels", "guilds", "webhooks", "interactions"];
+
+/// The resources that are followed by a parameter that isn't an id, and the name of the parameter
+const NAMED_PARAMETERS: [(&str, &str); 3] = [
+    ("reactions", "emoji"),
+    ("invites", "invite_code"),
+    ("templates", "template_code"),
+];
+
+impl RequestRoute {
+    /**
+     * Works out the bucket key of a request from its path, for requests that aren't made with a `Route`.
+     * Ids are replaced with a parameter named after the resource before them, like `/channels/{channel_id}/messages/{message_id}`,
+     * and the id of a channel, guild, webhook or interaction at the start of the path is used as the major parameter.
+     * The token after a webhook's id is part of the major parameter, and the token after an interaction's id is left out of the key.
+     * Reaction emojis and invite and template codes are replaced with their parameter too, so that they share a bucket
+     * @param path The path of the request, without the base url
+     */
+    pub fn from_path(path: &str) -> RequestRoute {
+        let path = path.split('?').next().unwrap_or_default();
+        let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+
+        let mut base_route = String::new();
+        let mut major_param = String::new();
+        for (i, segment) in segments.iter().enumerate() {
+            let resource = i.checked_sub(1).map(|prev| segments[prev]);
+            let parent = i.checked_sub(2).map(|prev| segments[prev]);
+            let named_parameter = NAMED_PARAMETERS
+                .iter()
+                .find(|(name, _)| Some(*name) == resource)
+                .map(|(_, parameter)| parameter);
+
+            let part = if let Some(parameter) = named_parameter {
+                format!("{{{}}}", parameter)
+            } else if !segment.is_empty() && segment.chars().all(|c| c.is_ascii_digit()) {
+                // The id of a resource, which is named after the resource. The user that reacted comes after the emoji of a reaction
+                let resource = match parent {
+                    Some("reactions") => "users",
+                    _ => resource.unwrap_or_default(),
+                };
+                if i == 1 && MAJOR_RESOURCES.contains(&resource) {
+                    major_param = segment.to_string();
+                }
+                format!("{{{}_id}}", resource.strip_suffix('s').unwrap_or(resource))
+            } else if i == 2 && matches!(parent, Some("webhooks") | Some("interactions")) {
+                // The token of a webhook or interaction
+                let parent = parent.unwrap_or_default();
+                if parent == "webhooks" {
+                    major_param = format!("{}/{}", major_param, segment);
+                }
+                format!("{{{}_token}}", parent.strip_suffix('s').unwrap_or(parent))
+            } else {
+                segment.to_string()
+            };
+            base_route.push('/');
+            base_route.push_str(&part);
+        }
+
+        RequestRoute {
+            base_route,
+            major_param,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::Method;
+
+    use crate::{core::http::rate_limit_client::RequestRoute, discord::snowflake::Snowflake};
+
+    use super::Route;
+
+    #[test]
+    fn fills_in_major_parameters() {
+        let route = Route::CreateMessage {
+            channel_id: "41771983423143937".to_string(),
+        };
+        assert_eq!(route.method(), Method::POST);
+        assert_eq!(route.path(), "/channels/41771983423143937/messages");
+        assert_eq!(
+            route.bucket(),
+            RequestRoute {
+                base_route: "/channels/{channel_id}/messages".to_string(),
+                major_param: "41771983423143937".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn leaves_other_parameters_out_of_the_bucket() {
+        let route = Route::GetUser {
+            user_id: "80351110224678912".to_string(),
+        };
+        assert_eq!(route.method(), Method::GET);
+        assert_eq!(route.path(), "/users/80351110224678912");
+        assert_eq!(
+            route.bucket(),
+            RequestRoute {
+                base_route: "/users/{user_id}".to_string(),
+                major_param: String::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn joins_webhook_ids_and_tokens() {
+        let route = Route::ExecuteWebhook {
+            webhook_id: Snowflake(223704706495545344),
+            webhook_token: "3d89bb7572e0fb30d8128367b3b1b44f".to_string(),
+        };
+        assert_eq!(route.method(), Method::POST);
+        assert_eq!(
+            route.path(),
+            "/webhooks/223704706495545344/3d89bb7572e0fb30d8128367b3b1b44f"
+        );
+        assert_eq!(
+            route.bucket(),
+            RequestRoute {
+                base_route: "/webhooks/{webhook_id}/{webhook_token}".to_string(),
+                major_param: "223704706495545344/3d89bb7572e0fb30d8128367b3b1b44f".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn matches_every_route() {
+        let id = || Snowflake(41771983423143937);
+        let token = || "aW50ZXJhY3Rpb246ODg2MDgyNzQ3NjUzNzQ1Njgz".to_string();
+        let routes = vec![
+            Route::GetGatewayBot {},
+            Route::GetUser {
+                user_id: "80351110224678912".to_string(),
+            },
+            Route::GetCurrentApplication {},
+            Route::CreateMessage {
+                channel_id: "41771983423143937".to_string(),
+            },
+            Route::GetGlobalCommands { application_id: id() },
+            Route::GetGlobalCommand {
+                application_id: id(),
+                command_id: id(),
+            },
+            Route::CreateGlobalCommand { application_id: id() },
+            Route::EditGlobalCommand {
+                application_id: id(),
+                command_id: id(),
+            },
+            Route::CreateInteractionResponse {
+                interaction_id: id(),
+                interaction_token: token(),
+            },
+            Route::EditOriginalInteractionResponse {
+                webhook_id: id(),
+                webhook_token: token(),
+            },
+            Route::CreateFollowupMessage {
+                webhook_id: id(),
+                webhook_token: token(),
+            },
+            Route::ExecuteWebhook {
+                webhook_id: id(),
+                webhook_token: token(),
+            },
+        ];
+
+        for route in routes {
+            assert_eq!(RequestRoute::from_path(&route.path()), route.bucket(), "{:?}", route);
+        }
+    }
+
+    #[test]
+    fn shares_buckets_between_emojis() {
+        let fire = RequestRoute::from_path("/channels/1/messages/2/reactions/%F0%9F%94%A5/@me");
+        let custom = RequestRoute::from_path("/channels/1/messages/2/reactions/party:41771983423143937/@me");
+        assert_eq!(fire, custom);
+        assert_eq!(fire.base_route, "/channels/{channel_id}/messages/{message_id}/reactions/{emoji}/@me");
+
+        let user = RequestRoute::from_path("/channels/1/messages/2/reactions/%F0%9F%94%A5/80351110224678912");
+        assert_eq!(user.base_route, "/channels/{channel_id}/messages/{message_id}/reactions/{emoji}/{user_id}");
+    }
+
+    #[test]
+    fn shares_buckets_between_codes() {
+        let invite = RequestRoute::from_path("/invites/discord-developers?with_counts=true");
+        assert_eq!(invite.base_route, "/invites/{invite_code}");
+        assert_eq!(invite, RequestRoute::from_path("/invites/abc"));
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    collections::VecDeque,
+    fmt::Display,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use hyper::{
+    body::Bytes, client::HttpConnector, header::HeaderMap, Body, Client, Method, Request, Response,
+    StatusCode, Uri,
+};
+use hyper_tls::HttpsConnector;
+
+use crate::BASE_URL;
+
+/// Sends requests for the http client. By default, requests are sent with hyper.
+/// Another transport can be used to send requests through something else, or to test the bot without sending requests to discord
+#[async_trait]
+pub trait HttpTransport: Send + Sync {
+    /**
+     * Sends a request and waits for its response
+     * @param request The request to send. Its url starts with the base url in the settings
+     */
+    async fn send(&self, request: Request<Body>) -> Result<Response<Body>, TransportError>;
+}
+
+/// An error from a transport, when a request couldn't be sent or didn't get a response
+#[derive(Debug)]
+pub struct TransportError {
+    /// Whether the request failed to connect, which means that it never reached discord
+    connect: bool,
+    source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl TransportError {
+    /// Creates an error for a request that might have reached discord before it failed
+    pub fn new(source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        TransportError {
+            connect: false,
+            source: source.into(),
+        }
+    }
+
+    /// Creates an error for a request that failed to connect, so it never reached discord
+    pub fn connect(source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        TransportError {
+            connect: true,
+            source: source.into(),
+        }
+    }
+
+    /// Whether the request failed to connect, which means that it is always safe to send again
+    pub fn is_connect(&self) -> bool {
+        self.connect
+    }
+}
+
+impl From<hyper::Error> for TransportError {
+    fn from(error: hyper::Error) -> Self {
+        TransportError {
+            connect: error.is_connect(),
+            source: Box::new(error),
+        }
+    }
+}
+
+impl Display for TransportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl std::error::Error for TransportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// The default transport, which sends requests with a hyper client over https
+pub struct HyperTransport {
+    client: Client<HttpsConnector<HttpConnector>>,
+}
+
+impl HyperTransport {
+    pub fn new() -> Self {
+        HyperTransport {
+            client: Client::builder().build::<_, Body>(HttpsConnector::new()),
+        }
+    }
+}
+
+impl Default for HyperTransport {
+    fn default() -> Self {
+        HyperTransport::new()
+    }
+}
+
+#[async_trait]
+impl HttpTransport for HyperTransport {
+    async fn send(&self, request: Request<Body>) -> Result<Response<Body>, TransportError> {
+        Ok(self.client.request(request).await?)
+    }
+}
+
+/// A request that was sent through a `MockTransport`
+#[derive(Clone, Debug)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub uri: Uri,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl RecordedRequest {
+    /// Parses the body of the request as json
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> serde_json::Result<T> {
+        serde_json::from_slice(&self.body)
+    }
+}
+
+/// A response that a `MockTransport` sends back for matching requests
+struct MockResponse {
+    method: Method,
+    path: String,
+    status: StatusCode,
+    body: String,
+    /// Whether the response is only sent for one request
+    once: bool,
+}
+
+/// A transport that doesn't send anything. It records every request, and responds with the responses that it was given.
+/// It can be cloned, so one copy can be given to the http client, and the other can be used to check the requests that were made.
+///
+/// ```rust,ignore
+/// let transport = MockTransport::new();
+/// transport.respond(Method::GET, "/users/@me", 200, r#"{"id": "1", "username": "bot", ...}"#);
+/// let bot = Bot::with_client(token, RLClient::with_transport(BasicHttpQueue::new(60), transport.clone()));
+/// ```
+#[derive(Clone)]
+pub struct MockTransport {
+    /// The path of the base url, which comes before the path of every request
+    base_path: String,
+    responses: Arc<Mutex<VecDeque<MockResponse>>>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl Default for MockTransport {
+    fn default() -> Self {
+        MockTransport::with_base_url(BASE_URL)
+    }
+}
+
+impl MockTransport {
+    pub fn new() -> Self {
+        MockTransport::default()
+    }
+
+    /// Creates a transport for a bot that has a different base url set in its settings
+    pub fn with_base_url(base_url: &str) -> Self {
+        MockTransport {
+            base_path: base_url
+                .parse::<Uri>()
+                .map(|uri| uri.path().trim_end_matches('/').to_string())
+                .unwrap_or_default(),
+            responses: Arc::new(Mutex::new(VecDeque::new())),
+            requests: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /**
+     * Responds to every request with the method and path with the response.
+     * Responses that were added first are used first, and requests that don't match any response get a 404
+     * @param method The method of the requests
+     * @param path The whole path of the requests after the base url, without the query. Like `/channels/123/messages`
+     * @param status The status code of the response
+     * @param body The body of the response
+     */
+    pub fn respond(&self, method: Method, path: &str, status: u16, body: &str) {
+        self.add_response(method, path, status, body, false);
+    }
+
+    /// Responds to the next request with the method and path with the response. See `respond`
+    pub fn respond_once(&self, method: Method, path: &str, status: u16, body: &str) {
+        self.add_response(method, path, status, body, true);
+    }
+
+    fn add_response(&self, method: Method, path: &str, status: u16, body: &str, once: bool) {
+        self.responses.lock().unwrap().push_back(MockResponse {
+            method,
+            path: path.to_string(),
+            status: StatusCode::from_u16(status).unwrap(),
+            body: body.to_string(),
+            once,
+        });
+    }
+
+    /// Gets every request that has been sent, in the order they were sent
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl HttpTransport for MockTransport {
+    async fn send(&self, request: Request<Body>) -> Result<Response<Body>, TransportError> {
+        let (parts, body) = request.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+
+        let path = parts.uri.path().strip_prefix(self.base_path.as_str());
+        let response = {
+            let mut responses = self.responses.lock().unwrap();
+            let index = responses.iter().position(|response| {
+                response.method == parts.method && path == Some(response.path.as_str())
+            });
+            match index {
+                Some(index) if responses[index].once => {
+                    let response = responses.remove(index).unwrap();
+                    Some((response.status, response.body))
+                }
+                Some(index) => Some((responses[index].status, responses[index].body.clone())),
+                None => None,
+            }
+        };
+
+        self.requests.lock().unwrap().push(RecordedRequest {
+            method: parts.method,
+            uri: parts.uri,
+            headers: parts.headers,
+            body,
+        });
+
+        let (status, body) = response.unwrap_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                r#"{"message": "404: Not Found", "code": 0}"#.to_string(),
+            )
+        });
+        let mut response = Response::new(Body::from(body));
+        *response.status_mut() = status;
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Body, Method};
+    use serde_json::Value;
+
+    use crate::core::{
+        abstraction::{context::Context, shutdown::ShutdownHandle},
+        http::{
+            rate_limit_client::{send_request, RLClient},
+            request_queue::BasicHttpQueue,
+            route::Route,
+        },
+        interactions::handler::gateway_handle::GatewayHandle,
+        settings::Settings,
+    };
+
+    use super::MockTransport;
+
+    fn context(client: &RLClient) -> Context {
+        Context {
+            token: "token".to_string(),
+            request_stream: client.get_req_sender(),
+            settings: Settings::default(),
+            shard_id: 0,
+            gateway: GatewayHandle::default(),
+            shutdown: ShutdownHandle::default(),
+            cache: (),
+        }
+    }
+
+    async fn get_user(ctx: &Context, user_id: &str) -> Result<Value, crate::util::error::Error> {
+        let route = Route::GetUser {
+            user_id: user_id.to_string(),
+        };
+        let request = route.request(ctx).body(Body::empty()).unwrap();
+        send_request(ctx.clone(), route.bucket(), request).await
+    }
+
+    #[tokio::test]
+    async fn sends_requests_through_the_transport() {
+        let transport = MockTransport::new();
+        transport.respond(Method::GET, "/users/1", 200, r#"{"id": "1"}"#);
+        let client = RLClient::with_transport(BasicHttpQueue::new(60), transport.clone());
+        let ctx = context(&client);
+
+        let user = get_user(&ctx, "1").await.unwrap();
+        assert_eq!(user["id"], "1");
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, Method::GET);
+        assert_eq!(requests[0].uri.path(), "/api/v9/users/1");
+        assert_eq!(requests[0].headers["authorization"], "Bot token");
+    }
+
+    #[tokio::test]
+    async fn only_matches_the_whole_path() {
+        let transport = MockTransport::new();
+        transport.respond(Method::GET, "/users/1", 200, r#"{"id": "1"}"#);
+        let client = RLClient::with_transport(BasicHttpQueue::new(60), transport.clone());
+        let ctx = context(&client);
+
+        assert!(get_user(&ctx, "21").await.is_err());
+        assert_eq!(transport.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sends_rate_limited_requests_again() {
+        let transport = MockTransport::new();
+        transport.respond_once(
+            Method::GET,
+            "/users/1",
+            429,
+            r#"{"message": "You are being rate limited.", "retry_after": 0.01, "global": false}"#,
+        );
+        transport.respond(Method::GET, "/users/1", 200, r#"{"id": "1"}"#);
+        let client = RLClient::with_transport(BasicHttpQueue::new(60), transport.clone());
+        let ctx = context(&client);
+
+        let user = get_user(&ctx, "1").await.unwrap();
+        assert_eq!(user["id"], "1");
+        assert_eq!(transport.requests().len(), 2);
+    }
+}
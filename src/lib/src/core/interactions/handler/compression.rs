@@ -0,0 +1,194 @@
+use std::fmt::Display;
+
+use flate2::{Decompress, DecompressError, FlushDecompress, Status};
+
+/// Every complete message on a `zlib-stream` connection ends with this suffix from the Z_SYNC_FLUSH
+const ZLIB_SUFFIX: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/**
+ * Decompresses the messages that are received on a `zlib-stream` gateway connection.
+ * Every frame on the connection shares the same inflate context, so there should be one of these per connection.
+ * A message can be split across multiple frames, so frames are buffered until the Z_SYNC_FLUSH suffix is received.
+ * @docs <https://discord.com/developers/docs/topics/gateway#transport-compression>
+ */
+pub struct ZlibStream {
+    /// The inflate context that is shared across every frame
+    inflater: Decompress,
+    /// Frames that have been received, but don't make up a full message yet
+    buffer: Vec<u8>,
+}
+
+/// An error from decompressing a message. The inflate context can't be used after one of these, so the connection has to be restarted
+#[derive(Debug)]
+pub enum InflateError {
+    /// The data isn't valid zlib
+    Corrupt(DecompressError),
+    /// The inflater stopped before it used all of the message, because the message is incomplete or comes after the end of the stream
+    Incomplete,
+}
+
+impl ZlibStream {
+    pub fn new() -> Self {
+        Self {
+            inflater: Decompress::new(true),
+            buffer: Vec::new(),
+        }
+    }
+
+    /**
+     * Adds a frame to the stream.
+     * @return The decompressed message if the frame completes one, otherwise None
+     */
+    pub fn push(&mut self, frame: &[u8]) -> Result<Option<Vec<u8>>, InflateError> {
+        self.buffer.extend_from_slice(frame);
+        if !self.buffer.ends_with(&ZLIB_SUFFIX) {
+            return Ok(None);
+        }
+
+        let result = self.inflate();
+        self.buffer.clear();
+        result.map(Some)
+    }
+
+    /// Decompresses everything in the buffer
+    fn inflate(&mut self) -> Result<Vec<u8>, InflateError> {
+        let mut output = Vec::with_capacity(self.buffer.len() * 4);
+        let mut offset = 0;
+        loop {
+            let total_in = self.inflater.total_in();
+            let total_out = self.inflater.total_out();
+            let status = self
+                .inflater
+                .decompress_vec(&self.buffer[offset..], &mut output, FlushDecompress::Sync)
+                .map_err(InflateError::Corrupt)?;
+            let consumed = (self.inflater.total_in() - total_in) as usize;
+            let produced = self.inflater.total_out() - total_out;
+            offset += consumed;
+
+            let has_room = output.len() < output.capacity();
+            // Stop once all of the input has been used, and the inflater had room to write everything out
+            if offset >= self.buffer.len() && has_room {
+                return Ok(output);
+            }
+            // Discord never ends the stream, and an inflater that had room but didn't do anything will never finish the message
+            if status == Status::StreamEnd || (consumed == 0 && produced == 0 && has_room) {
+                return Err(InflateError::Incomplete);
+            }
+            output.reserve(output.capacity());
+        }
+    }
+}
+
+impl Default for ZlibStream {
+    fn default() -> Self {
+        ZlibStream::new()
+    }
+}
+
+impl Display for InflateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            InflateError::Corrupt(error) => write!(f, "Invalid zlib data - {}", error),
+            InflateError::Incomplete => write!(f, "The zlib data is incomplete"),
+        }
+    }
+}
+
+impl std::error::Error for InflateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InflateError::Corrupt(error) => Some(error),
+            InflateError::Incomplete => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::{Compress, Compression, FlushCompress};
+
+    use super::{InflateError, ZlibStream, ZLIB_SUFFIX};
+
+    /// Compresses messages the way that discord does, with one deflate context and a sync flush after each message
+    fn compress(messages: &[&str]) -> Vec<Vec<u8>> {
+        let mut compress = Compress::new(Compression::default(), true);
+        messages
+            .iter()
+            .map(|message| {
+                let mut output = Vec::with_capacity(message.len() + 64);
+                compress
+                    .compress_vec(message.as_bytes(), &mut output, FlushCompress::Sync)
+                    .unwrap();
+                assert!(output.ends_with(&ZLIB_SUFFIX));
+                output
+            })
+            .collect()
+    }
+
+    #[test]
+    fn joins_messages_split_across_frames() {
+        let message = r#"{"op":10,"d":{"heartbeat_interval":41250}}"#;
+        let compressed = compress(&[message]).remove(0);
+        let mut stream = ZlibStream::new();
+
+        let (first, rest) = compressed.split_at(compressed.len() / 3);
+        let (second, third) = rest.split_at(rest.len() / 2);
+        assert_eq!(stream.push(first).unwrap(), None);
+        assert_eq!(stream.push(second).unwrap(), None);
+        assert_eq!(stream.push(third).unwrap().unwrap(), message.as_bytes());
+    }
+
+    #[test]
+    fn shares_the_context_between_messages() {
+        // Later messages refer back to the earlier ones, so they can only be read with the same context
+        let messages = [
+            r#"{"op":0,"s":1,"t":"MESSAGE_CREATE","d":{"content":"hello"}}"#,
+            r#"{"op":0,"s":2,"t":"MESSAGE_CREATE","d":{"content":"hello"}}"#,
+            r#"{"op":11,"d":null}"#,
+        ];
+        let mut stream = ZlibStream::new();
+        for (message, compressed) in messages.iter().zip(compress(&messages)) {
+            assert_eq!(stream.push(&compressed).unwrap().unwrap(), message.as_bytes());
+        }
+    }
+
+    #[test]
+    fn decompresses_large_messages() {
+        let message = "x".repeat(100_000);
+        let compressed = compress(&[&message]).remove(0);
+        assert_eq!(ZlibStream::new().push(&compressed).unwrap().unwrap(), message.as_bytes());
+    }
+
+    #[test]
+    fn stops_on_incomplete_messages() {
+        let message = r#"{"op":10,"d":{"heartbeat_interval":41250}}"#;
+        let compressed = compress(&[message]).remove(0);
+
+        // Every cut has to return instead of waiting for input that isn't there.
+        // A cut in the middle of a stored block can't be told apart from a shorter block, so that part of the message is returned and fails to parse.
+        // The last 5 bytes are the empty block from the sync flush, so the cuts stop before them
+        for cut in 2..compressed.len() - ZLIB_SUFFIX.len() - 1 {
+            let mut truncated = compressed[..cut].to_vec();
+            truncated.extend_from_slice(&ZLIB_SUFFIX);
+
+            let mut stream = ZlibStream::new();
+            if let Ok(Some(output)) = stream.push(&truncated) {
+                assert_ne!(output, message.as_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_data_after_the_end_of_the_stream() {
+        let mut compress = Compress::new(Compression::default(), true);
+        let mut frame = Vec::with_capacity(64);
+        compress
+            .compress_vec(b"bye", &mut frame, FlushCompress::Finish)
+            .unwrap();
+        frame.extend_from_slice(&ZLIB_SUFFIX);
+
+        let mut stream = ZlibStream::new();
+        assert!(matches!(stream.push(&frame), Err(InflateError::Incomplete)));
+        assert!(stream.push(&ZLIB_SUFFIX).is_err());
+    }
+}
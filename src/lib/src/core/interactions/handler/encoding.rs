@@ -0,0 +1,53 @@
+use serde::de::DeserializeOwned;
+use tokio_tungstenite::tungstenite::Message;
+
+#[cfg(feature = "etf")]
+use super::etf;
+
+/// The format that payloads are sent in over the gateway
+/// @docs <https://discord.com/developers/docs/topics/gateway#encoding-and-compression>
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GatewayEncoding {
+    Json,
+    /// Erlang External Term Format. Requires the `etf` feature
+    #[cfg(feature = "etf")]
+    Etf,
+}
+
+impl Default for GatewayEncoding {
+    fn default() -> Self {
+        GatewayEncoding::Json
+    }
+}
+
+impl GatewayEncoding {
+    /// Gets the value of the `encoding` query parameter for the gateway url
+    pub fn name(&self) -> &'static str {
+        match self {
+            GatewayEncoding::Json => "json",
+            #[cfg(feature = "etf")]
+            GatewayEncoding::Etf => "etf",
+        }
+    }
+
+    /// Parses a payload that was received from the gateway. Returns None if it can't be parsed
+    pub fn decode<T: DeserializeOwned>(&self, data: &[u8]) -> Option<T> {
+        match self {
+            GatewayEncoding::Json => serde_json::from_slice(data).ok(),
+            #[cfg(feature = "etf")]
+            GatewayEncoding::Etf => etf::from_slice(data).ok(),
+        }
+    }
+
+    /// Converts a json command into a message in this encoding
+    pub fn encode(&self, command: Message) -> Message {
+        match (self, command) {
+            #[cfg(feature = "etf")]
+            (GatewayEncoding::Etf, Message::Text(text)) => match serde_json::from_str(&text) {
+                Ok(value) => Message::Binary(etf::encode(&value)),
+                Err(_) => Message::Text(text),
+            },
+            (_, command) => command,
+        }
+    }
+}
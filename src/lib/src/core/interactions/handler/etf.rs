@@ -0,0 +1,751 @@
+use std::{fmt::Display, io::Read};
+
+use flate2::read::ZlibDecoder;
+use serde::{
+    de::{
+        self, value::SeqDeserializer, DeserializeOwned, DeserializeSeed, EnumAccess, IgnoredAny,
+        IntoDeserializer, MapAccess, SeqAccess, VariantAccess, Visitor,
+    },
+    forward_to_deserialize_any, Deserialize,
+};
+use serde_json::Value;
+
+/**
+ * Erlang External Term Format tags
+ * @docs <https://www.erlang.org/doc/apps/erts/erl_ext_dist.html>
+ */
+const FORMAT_VERSION: u8 = 131;
+const COMPRESSED: u8 = 80;
+const NEW_FLOAT_EXT: u8 = 70;
+const SMALL_INTEGER_EXT: u8 = 97;
+const INTEGER_EXT: u8 = 98;
+const FLOAT_EXT: u8 = 99;
+const ATOM_EXT: u8 = 100;
+const SMALL_TUPLE_EXT: u8 = 104;
+const LARGE_TUPLE_EXT: u8 = 105;
+const NIL_EXT: u8 = 106;
+const STRING_EXT: u8 = 107;
+const LIST_EXT: u8 = 108;
+const BINARY_EXT: u8 = 109;
+const SMALL_BIG_EXT: u8 = 110;
+const LARGE_BIG_EXT: u8 = 111;
+const SMALL_ATOM_EXT: u8 = 115;
+const MAP_EXT: u8 = 116;
+const ATOM_UTF8_EXT: u8 = 118;
+const SMALL_ATOM_UTF8_EXT: u8 = 119;
+
+/// The largest integer that a json number can hold without losing precision.
+/// Snowflakes are sent as integers in ETF, but as strings in json, and every snowflake is larger than this
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// An error from decoding an ETF payload
+#[derive(Debug)]
+pub struct EtfError(String);
+
+impl Display for EtfError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for EtfError {}
+
+impl de::Error for EtfError {
+    fn custom<T: Display>(msg: T) -> Self {
+        EtfError(msg.to_string())
+    }
+}
+
+/**
+ * Parses an ETF payload straight into a type, the same way that it would be parsed from the json encoding.
+ * Binaries and atoms become strings (apart from `nil`, `true` and `false`), and tuples become lists.
+ * Integers that are too large for a json number become strings, like the snowflakes in json payloads,
+ * and any integer can be read into a string field.
+ */
+pub fn from_slice<T: DeserializeOwned>(data: &[u8]) -> Result<T, EtfError> {
+    let mut decoder = Decoder { data, pos: 0 };
+    if decoder.read_u8()? != FORMAT_VERSION {
+        return Err(EtfError("Unknown ETF version".to_string()));
+    }
+    if decoder.peek_u8()? == COMPRESSED {
+        decoder.pos += 1;
+        let size = decoder.read_u32()? as usize;
+        let mut inflated = Vec::with_capacity(size);
+        ZlibDecoder::new(&data[decoder.pos..])
+            .read_to_end(&mut inflated)
+            .map_err(|e| EtfError(e.to_string()))?;
+        return T::deserialize(&mut Decoder {
+            data: &inflated,
+            pos: 0,
+        });
+    }
+    T::deserialize(&mut decoder)
+}
+
+/**
+ * Encodes a json value as ETF so that it can be sent to the gateway.
+ * Strings are encoded as binaries, and null is encoded as the `nil` atom.
+ */
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut out = vec![FORMAT_VERSION];
+    encode_term(value, &mut out);
+    out
+}
+
+/// Reads terms out of an ETF payload
+struct Decoder<'de> {
+    data: &'de [u8],
+    pos: usize,
+}
+
+impl<'de> Decoder<'de> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'de [u8], EtfError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| EtfError("Unexpected end of ETF payload".to_string()))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn peek_u8(&self) -> Result<u8, EtfError> {
+        self.data
+            .get(self.pos)
+            .copied()
+            .ok_or_else(|| EtfError("Unexpected end of ETF payload".to_string()))
+    }
+
+    fn read_u8(&mut self) -> Result<u8, EtfError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, EtfError> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, EtfError> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Reads the text of an atom, after its tag
+    fn read_atom(&mut self, tag: u8) -> Result<&'de str, EtfError> {
+        let len = match tag {
+            ATOM_EXT | ATOM_UTF8_EXT => self.read_u16()? as usize,
+            _ => self.read_u8()? as usize,
+        };
+        std::str::from_utf8(self.read_bytes(len)?).map_err(|e| EtfError(e.to_string()))
+    }
+
+    /// Reads an integer, after its tag
+    fn read_integer(&mut self, tag: u8) -> Result<i128, EtfError> {
+        match tag {
+            SMALL_INTEGER_EXT => Ok(self.read_u8()? as i128),
+            INTEGER_EXT => Ok(self.read_u32()? as i32 as i128),
+            SMALL_BIG_EXT => {
+                let len = self.read_u8()? as usize;
+                self.read_big(len)
+            }
+            _ => {
+                let len = self.read_u32()? as usize;
+                self.read_big(len)
+            }
+        }
+    }
+
+    /// Snowflakes are sent as big integers, which are little endian with a separate sign byte
+    fn read_big(&mut self, len: usize) -> Result<i128, EtfError> {
+        let negative = self.read_u8()? != 0;
+        let bytes = self.read_bytes(len)?;
+        if len > 8 {
+            return Err(EtfError("ETF integer is too large".to_string()));
+        }
+        let magnitude = bytes
+            .iter()
+            .rev()
+            .fold(0u64, |n, byte| (n << 8) | *byte as u64) as i128;
+        Ok(if negative { -magnitude } else { magnitude })
+    }
+
+    /// Whether the next term is the `nil` atom, which is null in json
+    fn next_is_nil(&self) -> bool {
+        let mut peek = Decoder {
+            data: self.data,
+            pos: self.pos,
+        };
+        match peek.read_u8() {
+            Ok(tag @ (ATOM_EXT | ATOM_UTF8_EXT | SMALL_ATOM_EXT | SMALL_ATOM_UTF8_EXT)) => {
+                matches!(peek.read_atom(tag), Ok("nil" | "null"))
+            }
+            _ => false,
+        }
+    }
+
+    /// Lets a visitor read the next `len` terms as a list or map, and then skips any that it didn't read
+    fn visit_terms<V: Visitor<'de>>(
+        &mut self,
+        len: usize,
+        visitor: V,
+        map: bool,
+    ) -> Result<V::Value, EtfError> {
+        let mut terms = Terms {
+            decoder: self,
+            remaining: if map { len * 2 } else { len },
+        };
+        let value = if map {
+            visitor.visit_map(&mut terms)?
+        } else {
+            visitor.visit_seq(&mut terms)?
+        };
+        for _ in 0..terms.remaining {
+            IgnoredAny::deserialize(&mut *self)?;
+        }
+        Ok(value)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Decoder<'de> {
+    type Error = EtfError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EtfError> {
+        match self.read_u8()? {
+            NEW_FLOAT_EXT => {
+                let bytes = self.read_bytes(8)?;
+                visitor.visit_f64(f64::from_be_bytes(bytes.try_into().unwrap()))
+            }
+            FLOAT_EXT => {
+                let bytes = self.read_bytes(31)?;
+                let float: f64 = String::from_utf8_lossy(bytes)
+                    .trim_end_matches('\0')
+                    .parse()
+                    .map_err(|_| EtfError("Invalid ETF float".to_string()))?;
+                visitor.visit_f64(float)
+            }
+            tag @ (SMALL_INTEGER_EXT | INTEGER_EXT | SMALL_BIG_EXT | LARGE_BIG_EXT) => {
+                let n = self.read_integer(tag)?;
+                if n.unsigned_abs() > MAX_SAFE_INTEGER as u128 {
+                    visitor.visit_string(n.to_string())
+                } else if n < 0 {
+                    visitor.visit_i64(n as i64)
+                } else {
+                    visitor.visit_u64(n as u64)
+                }
+            }
+            tag @ (ATOM_EXT | ATOM_UTF8_EXT | SMALL_ATOM_EXT | SMALL_ATOM_UTF8_EXT) => {
+                match self.read_atom(tag)? {
+                    "nil" | "null" => visitor.visit_unit(),
+                    "true" => visitor.visit_bool(true),
+                    "false" => visitor.visit_bool(false),
+                    atom => visitor.visit_borrowed_str(atom),
+                }
+            }
+            BINARY_EXT => {
+                let len = self.read_u32()? as usize;
+                let bytes = self.read_bytes(len)?;
+                match std::str::from_utf8(bytes) {
+                    Ok(s) => visitor.visit_borrowed_str(s),
+                    Err(_) => visitor.visit_string(String::from_utf8_lossy(bytes).into_owned()),
+                }
+            }
+            // Lists of small integers are sent as strings
+            STRING_EXT => {
+                let len = self.read_u16()? as usize;
+                let bytes = self.read_bytes(len)?;
+                visitor.visit_seq(SeqDeserializer::<_, EtfError>::new(bytes.iter().copied()))
+            }
+            NIL_EXT => self.visit_terms(0, visitor, false),
+            LIST_EXT => {
+                let len = self.read_u32()? as usize;
+                let value = self.visit_terms(len, visitor, false)?;
+                // Proper lists end with an empty list as their tail
+                IgnoredAny::deserialize(&mut *self)?;
+                Ok(value)
+            }
+            SMALL_TUPLE_EXT => {
+                let len = self.read_u8()? as usize;
+                self.visit_terms(len, visitor, false)
+            }
+            LARGE_TUPLE_EXT => {
+                let len = self.read_u32()? as usize;
+                self.visit_terms(len, visitor, false)
+            }
+            MAP_EXT => {
+                let len = self.read_u32()? as usize;
+                self.visit_terms(len, visitor, true)
+            }
+            tag => Err(EtfError(format!("Unsupported ETF tag {}", tag))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EtfError> {
+        if self.next_is_nil() {
+            IgnoredAny::deserialize(&mut *self)?;
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    /// Integers can be read as strings, since fields that are strings in json are often integers in ETF
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EtfError> {
+        match self.peek_u8()? {
+            tag @ (SMALL_INTEGER_EXT | INTEGER_EXT | SMALL_BIG_EXT | LARGE_BIG_EXT) => {
+                self.pos += 1;
+                visitor.visit_string(self.read_integer(tag)?.to_string())
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, EtfError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, EtfError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    /// Enums are read like json, either as a string for a unit variant, or as a map with a single key
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, EtfError> {
+        if self.peek_u8()? == MAP_EXT {
+            self.pos += 1;
+            if self.read_u32()? != 1 {
+                return Err(EtfError("Expected a map with a single key for an enum".to_string()));
+            }
+            visitor.visit_enum(Enum { decoder: self })
+        } else {
+            let variant = String::deserialize(&mut *self)?;
+            visitor.visit_enum(variant.into_deserializer())
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char bytes byte_buf unit unit_struct
+        seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// The terms of a list or map, which are read one after another
+struct Terms<'a, 'de> {
+    decoder: &'a mut Decoder<'de>,
+    remaining: usize,
+}
+
+impl<'de, 'a> SeqAccess<'de> for Terms<'a, 'de> {
+    type Error = EtfError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, EtfError> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.decoder).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+impl<'de, 'a> MapAccess<'de> for Terms<'a, 'de> {
+    type Error = EtfError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, EtfError> {
+        self.next_element_seed(seed)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, EtfError> {
+        self.remaining -= 1;
+        seed.deserialize(&mut *self.decoder)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining / 2)
+    }
+}
+
+/// An enum variant that is written as a map with a single key
+struct Enum<'a, 'de> {
+    decoder: &'a mut Decoder<'de>,
+}
+
+impl<'de, 'a> EnumAccess<'de> for Enum<'a, 'de> {
+    type Error = EtfError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), EtfError> {
+        let variant = seed.deserialize(&mut *self.decoder)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
+    type Error = EtfError;
+
+    fn unit_variant(self) -> Result<(), EtfError> {
+        IgnoredAny::deserialize(self.decoder).map(|_| ())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, EtfError> {
+        seed.deserialize(self.decoder)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, EtfError> {
+        de::Deserializer::deserialize_seq(self.decoder, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, EtfError> {
+        de::Deserializer::deserialize_map(self.decoder, visitor)
+    }
+}
+
+fn encode_term(value: &Value, out: &mut Vec<u8>) {
+    match value {
+        Value::Null => encode_atom("nil", out),
+        Value::Bool(b) => encode_atom(if *b { "true" } else { "false" }, out),
+        Value::Number(n) => {
+            if let Some(n) = n.as_u64() {
+                if n <= u8::MAX as u64 {
+                    out.push(SMALL_INTEGER_EXT);
+                    out.push(n as u8);
+                } else if n <= i32::MAX as u64 {
+                    out.push(INTEGER_EXT);
+                    out.extend_from_slice(&(n as i32).to_be_bytes());
+                } else {
+                    encode_big(false, n, out);
+                }
+            } else if let Some(n) = n.as_i64() {
+                if n >= i32::MIN as i64 {
+                    out.push(INTEGER_EXT);
+                    out.extend_from_slice(&(n as i32).to_be_bytes());
+                } else {
+                    encode_big(true, n.unsigned_abs(), out);
+                }
+            } else {
+                out.push(NEW_FLOAT_EXT);
+                out.extend_from_slice(&n.as_f64().unwrap_or_default().to_be_bytes());
+            }
+        }
+        Value::String(s) => {
+            out.push(BINARY_EXT);
+            out.extend_from_slice(&(s.len() as u32).to_be_bytes());
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(list) => {
+            if !list.is_empty() {
+                out.push(LIST_EXT);
+                out.extend_from_slice(&(list.len() as u32).to_be_bytes());
+                for item in list {
+                    encode_term(item, out);
+                }
+            }
+            out.push(NIL_EXT);
+        }
+        Value::Object(map) => {
+            out.push(MAP_EXT);
+            out.extend_from_slice(&(map.len() as u32).to_be_bytes());
+            for (key, value) in map {
+                encode_term(&Value::String(key.clone()), out);
+                encode_term(value, out);
+            }
+        }
+    }
+}
+
+fn encode_atom(atom: &str, out: &mut Vec<u8>) {
+    out.push(SMALL_ATOM_UTF8_EXT);
+    out.push(atom.len() as u8);
+    out.extend_from_slice(atom.as_bytes());
+}
+
+fn encode_big(negative: bool, magnitude: u64, out: &mut Vec<u8>) {
+    let bytes: Vec<u8> = magnitude
+        .to_le_bytes()
+        .into_iter()
+        .rev()
+        .skip_while(|b| *b == 0)
+        .collect::<Vec<u8>>()
+        .into_iter()
+        .rev()
+        .collect();
+    out.push(SMALL_BIG_EXT);
+    out.push(bytes.len() as u8);
+    out.push(negative as u8);
+    out.extend_from_slice(&bytes);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::ZlibEncoder, Compression};
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+
+    use crate::{
+        core::interactions::handler::{
+            events::ready_payload::ReadyPayloadData, gateway_payload::PayloadBase,
+        },
+        discord::resources::channel::message::Message,
+    };
+
+    use super::*;
+
+    /// Decodes a single term into json
+    fn decode(term: &[u8]) -> Value {
+        let mut data = vec![FORMAT_VERSION];
+        data.extend_from_slice(term);
+        from_slice(&data).unwrap()
+    }
+
+    fn big(tag: u8, negative: bool, n: u64) -> Vec<u8> {
+        let mut term = vec![tag];
+        match tag {
+            SMALL_BIG_EXT => term.push(8),
+            _ => term.extend_from_slice(&8u32.to_be_bytes()),
+        }
+        term.push(negative as u8);
+        term.extend_from_slice(&n.to_le_bytes());
+        term
+    }
+
+    #[test]
+    fn decodes_integers() {
+        assert_eq!(decode(&[SMALL_INTEGER_EXT, 42]), json!(42));
+        assert_eq!(decode(&[INTEGER_EXT, 0xFF, 0xFF, 0xFF, 0xFE]), json!(-2));
+        assert_eq!(decode(&[SMALL_BIG_EXT, 2, 0, 0x39, 0x30]), json!(12345));
+        assert_eq!(decode(&[SMALL_BIG_EXT, 1, 1, 5]), json!(-5));
+        assert_eq!(decode(&big(SMALL_BIG_EXT, false, MAX_SAFE_INTEGER)), json!(MAX_SAFE_INTEGER));
+        // Snowflakes are too large for json numbers, so they are strings like in json payloads
+        assert_eq!(decode(&big(SMALL_BIG_EXT, false, 80351110224678912)), json!("80351110224678912"));
+        assert_eq!(decode(&big(LARGE_BIG_EXT, false, 80351110224678912)), json!("80351110224678912"));
+        assert_eq!(decode(&big(SMALL_BIG_EXT, true, 80351110224678912)), json!("-80351110224678912"));
+    }
+
+    #[test]
+    fn decodes_floats() {
+        let mut term = vec![NEW_FLOAT_EXT];
+        term.extend_from_slice(&1.5f64.to_be_bytes());
+        assert_eq!(decode(&term), json!(1.5));
+
+        let mut term = vec![FLOAT_EXT];
+        term.extend_from_slice(b"2.50000000000000000000e+00");
+        term.resize(32, 0);
+        assert_eq!(decode(&term), json!(2.5));
+    }
+
+    #[test]
+    fn decodes_atoms() {
+        assert_eq!(decode(&[ATOM_EXT, 0, 4, b't', b'e', b's', b't']), json!("test"));
+        assert_eq!(decode(&[SMALL_ATOM_EXT, 3, b'n', b'i', b'l']), Value::Null);
+        assert_eq!(decode(&[ATOM_UTF8_EXT, 0, 4, b't', b'r', b'u', b'e']), json!(true));
+        assert_eq!(decode(&[SMALL_ATOM_UTF8_EXT, 5, b'f', b'a', b'l', b's', b'e']), json!(false));
+    }
+
+    #[test]
+    fn decodes_binaries_and_strings() {
+        assert_eq!(decode(&[BINARY_EXT, 0, 0, 0, 2, b'h', b'i']), json!("hi"));
+        // STRING_EXT is a list of small integers
+        assert_eq!(decode(&[STRING_EXT, 0, 3, 1, 2, 3]), json!([1, 2, 3]));
+    }
+
+    #[test]
+    fn decodes_lists_and_tuples() {
+        assert_eq!(decode(&[NIL_EXT]), json!([]));
+        assert_eq!(
+            decode(&[LIST_EXT, 0, 0, 0, 2, SMALL_INTEGER_EXT, 1, SMALL_INTEGER_EXT, 2, NIL_EXT]),
+            json!([1, 2])
+        );
+        assert_eq!(
+            decode(&[SMALL_TUPLE_EXT, 2, SMALL_INTEGER_EXT, 1, BINARY_EXT, 0, 0, 0, 1, b'a']),
+            json!([1, "a"])
+        );
+        assert_eq!(decode(&[LARGE_TUPLE_EXT, 0, 0, 0, 1, SMALL_INTEGER_EXT, 7]), json!([7]));
+    }
+
+    #[test]
+    fn decodes_maps() {
+        let term = [
+            MAP_EXT, 0, 0, 0, 2,
+            SMALL_ATOM_UTF8_EXT, 1, b'a', SMALL_INTEGER_EXT, 1,
+            BINARY_EXT, 0, 0, 0, 1, b'b', NIL_EXT,
+        ];
+        assert_eq!(decode(&term), json!({"a": 1, "b": []}));
+    }
+
+    #[test]
+    fn decodes_compressed_terms() {
+        let term = [BINARY_EXT, 0, 0, 0, 2, b'h', b'i'];
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&term).unwrap();
+
+        let mut compressed = vec![COMPRESSED];
+        compressed.extend_from_slice(&(term.len() as u32).to_be_bytes());
+        compressed.extend_from_slice(&encoder.finish().unwrap());
+        assert_eq!(decode(&compressed), json!("hi"));
+    }
+
+    #[test]
+    fn rejects_unknown_terms() {
+        assert!(from_slice::<Value>(&[FORMAT_VERSION, 200]).is_err());
+        assert!(from_slice::<Value>(&[130, SMALL_INTEGER_EXT, 1]).is_err());
+        assert!(from_slice::<Value>(&[FORMAT_VERSION, BINARY_EXT, 0, 0, 0, 9]).is_err());
+    }
+
+    #[test]
+    fn reads_integers_into_strings() {
+        #[derive(Deserialize)]
+        struct Ids {
+            small: String,
+            snowflake: String,
+            missing: Option<String>,
+        }
+
+        let value = json!({"small": 5, "snowflake": "80351110224678912", "missing": null});
+        let ids: Ids = from_slice(&discord_etf(&value)).unwrap();
+        assert_eq!(ids.snowflake, "80351110224678912");
+        assert!(ids.missing.is_none());
+
+        let mut data = vec![FORMAT_VERSION, MAP_EXT, 0, 0, 0, 2];
+        data.extend_from_slice(&[SMALL_ATOM_UTF8_EXT, 5, b's', b'm', b'a', b'l', b'l', SMALL_INTEGER_EXT, 5]);
+        data.extend_from_slice(&[SMALL_ATOM_UTF8_EXT, 9]);
+        data.extend_from_slice(b"snowflake");
+        data.extend_from_slice(&big(SMALL_BIG_EXT, false, 80351110224678912));
+        let ids: Ids = from_slice(&data).unwrap();
+        assert_eq!(ids.small, "5");
+        assert_eq!(ids.snowflake, "80351110224678912");
+    }
+
+    #[test]
+    fn decodes_what_it_encodes() {
+        let value = json!({"op": 2, "d": {"token": "abc", "large_threshold": 250, "shard": [0, 1], "presence": null, "big": 5000000000u64, "negative": -3000000000i64}});
+        assert_eq!(from_slice::<Value>(&encode(&value)).unwrap(), value);
+    }
+
+    /// Encodes a json payload the way that discord sends it in ETF, with atoms as keys and snowflakes as big integers
+    fn discord_etf(value: &Value) -> Vec<u8> {
+        fn term(value: &Value, out: &mut Vec<u8>) {
+            match value {
+                Value::String(s) if s.parse::<u64>().map_or(false, |n| n > MAX_SAFE_INTEGER) => {
+                    let n: u64 = s.parse().unwrap();
+                    encode_big(false, n, out);
+                }
+                Value::Object(map) => {
+                    out.push(MAP_EXT);
+                    out.extend_from_slice(&(map.len() as u32).to_be_bytes());
+                    for (key, value) in map {
+                        encode_atom(key, out);
+                        term(value, out);
+                    }
+                }
+                Value::Array(list) if !list.is_empty() => {
+                    out.push(LIST_EXT);
+                    out.extend_from_slice(&(list.len() as u32).to_be_bytes());
+                    for item in list {
+                        term(item, out);
+                    }
+                    out.push(NIL_EXT);
+                }
+                value => encode_term(value, out),
+            }
+        }
+        let mut out = vec![FORMAT_VERSION];
+        term(value, &mut out);
+        out
+    }
+
+    const READY: &str = r#"{
+        "op": 0,
+        "s": 1,
+        "t": "READY",
+        "d": {
+            "v": 9,
+            "user": {"id": "80351110224678912", "username": "Nelly", "discriminator": "1337", "avatar": "8342729096ea3675442027381ff50dfe", "bot": true, "verified": true, "flags": 64},
+            "guilds": [{"id": "41771983423143937", "unavailable": true}, {"id": "41771983444115456", "unavailable": true}],
+            "session_id": "b9bba3b3a1e3f53b33c2da1eb2f1e0e5",
+            "resume_gateway_url": "wss://gateway-us-east1-b.discord.gg",
+            "shard": [0, 1],
+            "application": {"id": "80351110224678912", "flags": 0}
+        }
+    }"#;
+
+    const MESSAGE_CREATE: &str = r#"{
+        "op": 0,
+        "s": 2,
+        "t": "MESSAGE_CREATE",
+        "d": {
+            "id": "334385199974967042",
+            "channel_id": "290926798999357250",
+            "guild_id": "290926798626357250",
+            "author": {"id": "53908099506183680", "username": "Mason", "discriminator": "9999", "avatar": "a_bab14f271d565501444b2ca3be944b25", "public_flags": 131141},
+            "member": {"roles": ["41771983423143936"], "joined_at": "2015-04-26T06:26:56.936000+00:00", "deaf": false, "mute": false, "nick": null},
+            "content": "Supa Hot",
+            "timestamp": "2017-07-11T17:27:07.299000+00:00",
+            "edited_timestamp": null,
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": [],
+            "nonce": "334385199974967042",
+            "pinned": false,
+            "type": 19,
+            "flags": 0,
+            "message_reference": {"message_id": "306588351130107906", "channel_id": "278325129692446722", "guild_id": "278325129692446720"}
+        }
+    }"#;
+
+    #[test]
+    fn ready_matches_json() {
+        let json: Value = serde_json::from_str(READY).unwrap();
+        let etf = discord_etf(&json);
+        assert_eq!(from_slice::<Value>(&etf).unwrap(), json);
+
+        let from_etf: PayloadBase<ReadyPayloadData> = from_slice(&etf).unwrap();
+        let from_json: PayloadBase<ReadyPayloadData> = serde_json::from_str(READY).unwrap();
+        assert_eq!(from_etf.data.user.id, from_json.data.user.id);
+        assert_eq!(from_etf.data.session_id, from_json.data.session_id);
+        assert_eq!(from_etf.data.shard, from_json.data.shard);
+        assert_eq!(
+            from_etf.data.guilds.iter().map(|g| g.id).collect::<Vec<_>>(),
+            from_json.data.guilds.iter().map(|g| g.id).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn message_create_matches_json() {
+        let json: Value = serde_json::from_str(MESSAGE_CREATE).unwrap();
+        let etf = discord_etf(&json);
+        assert_eq!(from_slice::<Value>(&etf).unwrap(), json);
+
+        let from_etf: PayloadBase<Message> = from_slice(&etf).unwrap();
+        let from_json: PayloadBase<Message> = serde_json::from_str(MESSAGE_CREATE).unwrap();
+        assert_eq!(
+            serde_json::to_value(&from_etf.data).unwrap(),
+            serde_json::to_value(&from_json.data).unwrap()
+        );
+        let reference = from_etf.data.message_reference.unwrap();
+        assert_eq!(reference.guild_id.as_deref(), Some("278325129692446720"));
+    }
+}
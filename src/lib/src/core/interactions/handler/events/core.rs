@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 
-use crate::core::interactions::handler::gateway_payload::PayloadOpcode;
+use crate::{
+    core::interactions::handler::gateway_payload::PayloadOpcode, discord::snowflake::Snowflake,
+};
 
 use super::PayloadData;
 
@@ -26,3 +28,57 @@ impl PayloadData for HeartBeatPayloadData {
         PayloadOpcode::Heartbeat
     }
 }
+
+#[derive(Serialize, Deserialize)]
+/**
+   Used to replay missed events when a disconnected client resumes.
+   @docs <https://discord.com/developers/docs/topics/gateway#resume>
+*/
+pub struct ResumePayloadData {
+    /// session token
+    pub token: String,
+    /// session id
+    pub session_id: String,
+    /// last sequence number received
+    pub seq: HeartBeatPayloadData,
+}
+impl PayloadData for ResumePayloadData {
+    fn get_opcode(&self) -> PayloadOpcode {
+        PayloadOpcode::Resume
+    }
+}
+
+/**
+   Sent to indicate that the session has been invalidated. The inner value indicates whether the session may be resumable.
+   @docs <https://discord.com/developers/docs/topics/gateway#invalid-session>
+*/
+pub type InvalidSessionPayloadData = bool;
+
+#[derive(Serialize, Deserialize)]
+/**
+   Used to request all members for a guild or a list of guilds. The members are sent back in Guild Members Chunk events.
+   @docs <https://discord.com/developers/docs/topics/gateway#request-guild-members>
+*/
+pub struct RequestGuildMembersPayloadData {
+    /// id of the guild to get members for
+    pub guild_id: Snowflake,
+    /// string that username starts with, or an empty string to return all members
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    /// maximum number of members to send matching the query; a limit of 0 can be used with an empty string query to return all members
+    pub limit: u64,
+    /// used to specify if we want the presences of the matched members
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presences: Option<bool>,
+    /// used to specify which users you wish to fetch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_ids: Option<Vec<Snowflake>>,
+    /// nonce to identify the Guild Members Chunk response
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+impl PayloadData for RequestGuildMembersPayloadData {
+    fn get_opcode(&self) -> PayloadOpcode {
+        PayloadOpcode::RequestGuildMembers
+    }
+}
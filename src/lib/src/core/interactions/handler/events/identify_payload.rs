@@ -1,23 +1,46 @@
 use bitflags::bitflags;
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::discord::gateway::presence::PresenceUpdate;
+use crate::{
+    core::interactions::handler::gateway_payload::PayloadOpcode,
+    discord::gateway::presence::UpdatePresence,
+};
 
-#[derive(Serialize, Deserialize)]
+use super::PayloadData;
+
+#[derive(Serialize, Deserialize, Clone)]
 /**
  * Used to trigger the initial handshake with the gateway.
  * @docs <https://discord.com/developers/docs/topics/gateway#identify-identify-structure>
 */
 pub struct IdentifyPayloadData {
+    /// authentication token
     pub token: String,
+    /// connection properties
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub properties: Option<IdentifyProperties>,
+    /// whether this connection supports compression of packets
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub compress: Option<bool>,
+    /// value between 50 and 250, total number of members where the gateway will stop sending offline members in the guild member list
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub large_threshold: Option<u64>,
+    /// used for Guild Sharding
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub shard: Option<[u64; 2]>,
-    pub presence: Option<PresenceUpdate>,
+    /// presence structure for initial presence information
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence: Option<UpdatePresence>,
+    /// the Gateway Intents you wish to receive
     pub intents: Intents,
 }
 
+impl PayloadData for IdentifyPayloadData {
+    fn get_opcode(&self) -> PayloadOpcode {
+        PayloadOpcode::Identify
+    }
+}
+
 impl IdentifyPayloadData {
     pub fn new(token: String) -> Self {
         Self {
@@ -34,8 +57,9 @@ impl IdentifyPayloadData {
 
 /**
  * Identify Connection Properties
+ * @docs <https://discord.com/developers/docs/topics/gateway#identify-identify-connection-properties>
  */
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct IdentifyProperties {
     /// your operating system
     #[serde(rename = "$os")]
@@ -48,8 +72,22 @@ pub struct IdentifyProperties {
     pub device: String,
 }
 
+impl Default for IdentifyProperties {
+    fn default() -> Self {
+        Self {
+            os: std::env::consts::OS.to_string(),
+            browser: "discrab".to_string(),
+            device: "discrab".to_string(),
+        }
+    }
+}
+
 bitflags! {
-    #[derive(Serialize)]
+    /**
+     * Gateway Intents
+     * GUILD_MEMBERS, GUILD_PRESENCES and MESSAGE_CONTENT are privileged, and have to be enabled in the developer portal before they can be used.
+     * @docs <https://discord.com/developers/docs/topics/gateway#gateway-intents>
+     */
     pub struct Intents: u64 {
         const GUILDS = 1 << 0;
         const GUILD_MEMBERS = 1 << 1;
@@ -66,10 +104,32 @@ bitflags! {
         const DIRECT_MESSAGES = 1 << 12;
         const DIRECT_MESSAGE_REACTIONS = 1 << 13;
         const DIRECT_MESSAGE_TYPING = 1 << 14;
+        const MESSAGE_CONTENT = 1 << 15;
         const GUILD_SCHEDULED_EVENTS = 1 << 16;
     }
 }
 
+impl Intents {
+    /// The intents that have to be enabled in the developer portal before they can be used
+    pub fn privileged() -> Self {
+        Self::GUILD_MEMBERS | Self::GUILD_PRESENCES | Self::MESSAGE_CONTENT
+    }
+
+    /// Every intent that can be used without being enabled in the developer portal
+    pub fn non_privileged() -> Self {
+        Self::all() - Self::privileged()
+    }
+}
+
+impl Serialize for Intents {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u64(self.bits())
+    }
+}
+
 impl<'de> Deserialize<'de> for Intents {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
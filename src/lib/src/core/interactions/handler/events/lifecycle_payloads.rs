@@ -0,0 +1,63 @@
+use discrab_codegen::CommandArg;
+use serde::{Deserialize, Serialize};
+
+use crate::core::abstraction::traits::CommandArg;
+
+/**
+ * Shard Connecting
+ * Sent by the library before a shard opens a connection to the gateway.
+ */
+#[derive(Serialize, Deserialize, Clone, CommandArg)]
+pub struct ShardConnecting {
+    /// the id of the shard
+    pub shard_id: u64,
+    /// whether the shard will try to resume its previous session instead of identifying
+    pub resuming: bool,
+}
+
+/**
+ * Shard Ready
+ * Sent by the library when a shard has identified and received the Ready event.
+ */
+#[derive(Serialize, Deserialize, Clone, CommandArg)]
+pub struct ShardReady {
+    /// the id of the shard
+    pub shard_id: u64,
+    /// the id of the shard's new session
+    pub session_id: String,
+}
+
+/**
+ * Shard Resumed
+ * Sent by the library when a shard has resumed its session, and every missed event has been replayed.
+ * @docs <https://discord.com/developers/docs/topics/gateway#resumed>
+ */
+#[derive(Serialize, Deserialize, Clone, CommandArg)]
+pub struct ShardResumed {
+    /// the id of the shard
+    pub shard_id: u64,
+}
+
+/**
+ * Shard Disconnected
+ * Sent by the library when a shard's connection to the gateway ends.
+ */
+#[derive(Serialize, Deserialize, Clone, CommandArg)]
+pub struct ShardDisconnected {
+    /// the id of the shard
+    pub shard_id: u64,
+    /// the close code that the connection was closed with, if there was one
+    pub close_code: Option<u16>,
+    /// whether the shard will connect again. False if the bot is shutting down, or if the close code can't be recovered from
+    pub will_reconnect: bool,
+}
+
+/**
+ * All Shards Ready
+ * Sent by the library once every shard that is run by this process has received the Ready event.
+ */
+#[derive(Serialize, Deserialize, Clone, CommandArg)]
+pub struct AllShardsReady {
+    /// the number of shards that are run by this process
+    pub shard_count: u64,
+}
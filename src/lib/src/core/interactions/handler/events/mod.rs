@@ -4,6 +4,7 @@ pub mod core;
 mod identify_payload;
 pub use identify_payload::*;
 pub mod dispatch_payloads;
+pub mod lifecycle_payloads;
 pub mod ready_payload;
 
 pub trait PayloadData {
@@ -32,6 +32,8 @@ pub struct ReadyPayloadData {
     pub guilds: Vec<UnavailableGuild>,
     /// used for resuming connections
     pub session_id: String,
+    /// gateway url for resuming connections
+    pub resume_gateway_url: Option<String>,
     /// the shard information associated with this session, if sent when identifying
     pub shard: Option<(u64, u64)>,
     /// contains id and flags
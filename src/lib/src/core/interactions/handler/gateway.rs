@@ -1,12 +1,11 @@
-use hyper::{Body, Method, Request};
+use hyper::Body;
 
 use crate::{
     core::{
         abstraction::context::Context,
-        http::rate_limit_client::{send_request, RequestRoute},
+        http::{rate_limit_client::send_request, route::Route},
     },
     util::error::Error,
-    BASE_URL,
 };
 use serde::Deserialize;
 
@@ -46,16 +45,12 @@ pub struct SessionStartLimit {
  * @docs <https://discord.com/developers/docs/topics/gateway#get-gateway-bot>
  */
 pub async fn get_gateway(ctx: Context) -> Result<Gateway, Error> {
-    let route = RequestRoute {
-        base_route: "/gateway/bot".to_string(),
-        major_param: "".to_string(),
-    };
-    let request_builder = Request::builder()
-        .method(Method::GET)
-        .uri(format!("{}/gateway/bot", BASE_URL))
+    let route = Route::GetGatewayBot {};
+    let request_builder = route
+        .request(&ctx)
         .header("content-type", "application/json")
         .body(Body::empty())
         .unwrap();
 
-    send_request(ctx, route, request_builder).await
+    send_request(ctx, route.bucket(), request_builder).await
 }
@@ -0,0 +1,400 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::oneshot;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{
+    discord::{
+        gateway::presence::PresenceUpdate, resources::guild::guild_member::GuildMember,
+        snowflake::Snowflake,
+    },
+    util::error::Error,
+};
+
+use super::{
+    events::{
+        core::RequestGuildMembersPayloadData, dispatch_payloads::GuildMembersChunk, PayloadData,
+    },
+    gateway_payload::PayloadBase,
+};
+
+/// How long to wait for every chunk of a guild member request before giving up
+const MEMBER_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Decides which members are returned by a guild member request
+/// @docs <https://discord.com/developers/docs/topics/gateway#request-guild-members>
+#[derive(Clone, Debug)]
+pub enum MemberFilter {
+    /// Members whose username starts with the query. An empty query with a limit of 0 returns every member, which requires the GUILD_MEMBERS intent
+    Query { query: String, limit: u64 },
+    /// The members with the given user ids
+    UserIds(Vec<Snowflake>),
+}
+
+/// Every member that was sent back for a guild member request
+#[derive(Clone, Default)]
+pub struct GuildMembers {
+    /// The members that matched the request
+    pub members: Vec<GuildMember>,
+    /// The presences of the members, if they were requested
+    pub presences: Vec<PresenceUpdate>,
+    /// The requested user ids that aren't members of the guild
+    pub not_found: Vec<Snowflake>,
+}
+
+/// A guild member request that is still receiving chunks
+struct PendingMembers {
+    /// The members that have been received so far
+    members: GuildMembers,
+    /// The indexes of the chunks that have been received
+    received: HashSet<u64>,
+    /// Resolves the request once every chunk has been received
+    done: oneshot::Sender<GuildMembers>,
+}
+
+/// Sends commands to the gateway connections that are run by this process.
+/// Commands go through each shard's sender, so they respect the gateway's send limit.
+#[derive(Clone)]
+pub struct GatewayHandle {
+    /// The command channel for each shard, by shard id
+    shards: Arc<Mutex<HashMap<u64, Sender<Message>>>>,
+    /// The total number of shards across every process
+    total_shards: Arc<AtomicU64>,
+    /// Guild member requests that are waiting for chunks, by nonce
+    pending_members: Arc<Mutex<HashMap<String, PendingMembers>>>,
+    /// Used to create a unique nonce for each guild member request
+    next_nonce: Arc<AtomicU64>,
+    /// The time between the last heartbeat and its acknowledgement for each shard, by shard id
+    latencies: Arc<Mutex<HashMap<u64, Duration>>>,
+    /// Errors that stopped a shard, which the bot can't recover from
+    fatal_errors: (Sender<Error>, Receiver<Error>),
+    /// How long to wait for every chunk of a guild member request
+    member_request_timeout: Duration,
+}
+
+impl Default for GatewayHandle {
+    fn default() -> Self {
+        Self {
+            shards: Default::default(),
+            total_shards: Default::default(),
+            pending_members: Default::default(),
+            next_nonce: Default::default(),
+            latencies: Default::default(),
+            fatal_errors: unbounded(),
+            member_request_timeout: MEMBER_REQUEST_TIMEOUT,
+        }
+    }
+}
+
+impl GatewayHandle {
+    /// Adds the command channel for a shard, so that commands can be sent through it
+    /// @param shard The shard as `[shard_id, num_shards]`
+    pub(crate) fn add_shard(&self, shard: [u64; 2], commands: Sender<Message>) {
+        self.total_shards.store(shard[1], Ordering::Relaxed);
+        self.shards.lock().unwrap().insert(shard[0], commands);
+    }
+
+    /// Reports an error that a shard can't recover from, which stops the bot
+    pub(crate) fn report_fatal(&self, error: Error) {
+        self.fatal_errors.0.send(error).ok();
+    }
+
+    /// Gets the channel that errors which stop the bot are sent through
+    pub(crate) fn fatal_errors(&self) -> Receiver<Error> {
+        self.fatal_errors.1.clone()
+    }
+
+    /// Gets the ids of the shards that are run by this process
+    pub fn shard_ids(&self) -> Vec<u64> {
+        self.shards.lock().unwrap().keys().copied().collect()
+    }
+
+    /// Records the time between a shard's last heartbeat and its acknowledgement
+    pub(crate) fn set_latency(&self, shard_id: u64, latency: Duration) {
+        self.latencies.lock().unwrap().insert(shard_id, latency);
+    }
+
+    /// Gets the round trip latency of a shard's gateway connection. None if it hasn't had a heartbeat acknowledged yet
+    pub fn latency(&self, shard_id: u64) -> Option<Duration> {
+        self.latencies.lock().unwrap().get(&shard_id).copied()
+    }
+
+    /// Gets the id of the shard that receives events for a guild
+    /// @docs <https://discord.com/developers/docs/topics/gateway#sharding-sharding-formula>
+    pub fn shard_for_guild(&self, guild_id: Snowflake) -> u64 {
+        // The timestamp is `guild_id >> 22`
+        guild_id.timestamp() % self.total_shards.load(Ordering::Relaxed).max(1)
+    }
+
+    /**
+     * Sends a command to a single shard
+     * @return false if the shard isn't run by this process
+     */
+    pub(crate) fn send_to_shard<T: PayloadData + Serialize>(&self, shard_id: u64, data: T) -> bool {
+        let command = Message::Text(serde_json::to_string(&PayloadBase::new(data)).unwrap());
+        match self.shards.lock().unwrap().get(&shard_id) {
+            Some(shard) => shard.send(command).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Sends a command to every shard
+    pub(crate) fn broadcast<T: PayloadData + Serialize>(&self, data: T) {
+        let command = serde_json::to_string(&PayloadBase::new(data)).unwrap();
+        for shard in self.shards.lock().unwrap().values() {
+            shard.send(Message::Text(command.clone())).ok();
+        }
+    }
+
+    /**
+     * Requests members of a guild through the shard that the guild is on, and waits for every chunk of the response
+     * @param guild_id The guild to get members for
+     * @param filter Which members to get
+     * @param presences Whether to get the presences of the members. Requires the GUILD_PRESENCES intent
+     */
+    pub(crate) async fn request_guild_members(
+        &self,
+        guild_id: Snowflake,
+        filter: MemberFilter,
+        presences: bool,
+    ) -> Result<GuildMembers, Error> {
+        let nonce = self.next_nonce.fetch_add(1, Ordering::Relaxed).to_string();
+        let (query, limit, user_ids) = match filter {
+            MemberFilter::Query { query, limit } => (Some(query), limit, None),
+            MemberFilter::UserIds(user_ids) => (None, 0, Some(user_ids)),
+        };
+
+        let (done, response) = oneshot::channel();
+        self.pending_members.lock().unwrap().insert(
+            nonce.clone(),
+            PendingMembers {
+                members: GuildMembers::default(),
+                received: HashSet::new(),
+                done,
+            },
+        );
+
+        let sent = self.send_to_shard(
+            self.shard_for_guild(guild_id),
+            RequestGuildMembersPayloadData {
+                guild_id,
+                query,
+                limit,
+                presences: Some(presences),
+                user_ids,
+                nonce: Some(nonce.clone()),
+            },
+        );
+        if !sent {
+            self.pending_members.lock().unwrap().remove(&nonce);
+            return Err(Error::Gateway(format!(
+                "The shard for guild {} isn't run by this process",
+                guild_id
+            )));
+        }
+
+        match async_std::future::timeout(self.member_request_timeout, response).await {
+            Ok(Ok(members)) => Ok(members),
+            _ => {
+                self.pending_members.lock().unwrap().remove(&nonce);
+                Err(Error::Gateway(format!(
+                    "Timed out waiting for the members of guild {}",
+                    guild_id
+                )))
+            }
+        }
+    }
+
+    /// Adds a Guild Members Chunk event to the request with the same nonce, and resolves the request once every chunk has been received.
+    /// This is called from the gateway thread so that a handler can wait for the chunks without blocking the events that deliver them.
+    pub(crate) fn receive_member_chunk(&self, data: &Value) {
+        let nonce = match data["nonce"].as_str() {
+            Some(nonce) => nonce,
+            None => return,
+        };
+        let mut pending_members = self.pending_members.lock().unwrap();
+        if !pending_members.contains_key(nonce) {
+            return;
+        }
+
+        let chunk: GuildMembersChunk = match serde_json::from_value(data.clone()) {
+            Ok(chunk) => chunk,
+            Err(_) => return,
+        };
+        let pending = pending_members.get_mut(nonce).unwrap();
+        if !pending.received.insert(chunk.chunk_index) {
+            return;
+        }
+        pending.members.members.extend(chunk.members);
+        pending
+            .members
+            .presences
+            .extend(chunk.presences.unwrap_or_default());
+        pending
+            .members
+            .not_found
+            .extend(chunk.not_found.unwrap_or_default());
+
+        if pending.received.len() as u64 >= chunk.chunk_count {
+            let pending = pending_members.remove(nonce).unwrap();
+            pending.done.send(pending.members).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crossbeam_channel::{unbounded, Receiver};
+    use futures_util::poll;
+    use serde_json::{json, Value};
+    use tokio_tungstenite::tungstenite::Message;
+
+    use crate::discord::snowflake::Snowflake;
+
+    use super::{GatewayHandle, MemberFilter};
+
+    const GUILD_ID: Snowflake = Snowflake(41771983423143937);
+
+    /// A gateway with a single shard, and the commands that are sent through it
+    fn gateway() -> (GatewayHandle, Receiver<Message>) {
+        let gateway = GatewayHandle {
+            member_request_timeout: Duration::from_millis(200),
+            ..GatewayHandle::default()
+        };
+        let (commands, sent) = unbounded();
+        gateway.add_shard([0, 1], commands);
+        (gateway, sent)
+    }
+
+    /// Gets the nonce of the Request Guild Members command that was sent
+    fn sent_nonce(sent: &Receiver<Message>) -> String {
+        let command: Value = match sent.try_recv().unwrap() {
+            Message::Text(text) => serde_json::from_str(&text).unwrap(),
+            message => panic!("Unexpected command {:?}", message),
+        };
+        assert_eq!(command["op"], 8);
+        command["d"]["nonce"].as_str().unwrap().to_string()
+    }
+
+    /// A Guild Members Chunk event with a member for each user id
+    fn chunk(nonce: &str, index: u64, count: u64, user_ids: &[&str]) -> Value {
+        let members: Vec<Value> = user_ids
+            .iter()
+            .map(|id| {
+                json!({
+                    "user": {"id": id, "username": "Nelly", "discriminator": "1337", "avatar": null},
+                    "roles": [],
+                    "joined_at": "2015-04-26T06:26:56.936000+00:00",
+                    "deaf": false,
+                    "mute": false,
+                })
+            })
+            .collect();
+        json!({
+            "guild_id": GUILD_ID,
+            "members": members,
+            "chunk_index": index,
+            "chunk_count": count,
+            "nonce": nonce,
+        })
+    }
+
+    fn user_ids(members: &super::GuildMembers) -> Vec<String> {
+        let mut ids: Vec<String> = members
+            .members
+            .iter()
+            .map(|member| member.user.as_ref().unwrap().id.to_string())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn finds_the_shard_for_a_guild() {
+        let gateway = GatewayHandle::default();
+        let (commands, _) = unbounded();
+        gateway.add_shard([0, 4], commands);
+
+        // (41771983423143937 >> 22) % 4 == 9959216934 % 4
+        assert_eq!(gateway.shard_for_guild(Snowflake(41771983423143937)), 2);
+        assert_eq!(gateway.shard_for_guild(Snowflake(4194304)), 1);
+        assert_eq!(gateway.shard_for_guild(Snowflake(0)), 0);
+    }
+
+    #[tokio::test]
+    async fn resolves_once_every_chunk_has_arrived() {
+        let (gateway, sent) = gateway();
+        let request = gateway.request_guild_members(GUILD_ID, MemberFilter::Query { query: String::new(), limit: 0 }, false);
+        tokio::pin!(request);
+        assert!(poll!(&mut request).is_pending());
+        let nonce = sent_nonce(&sent);
+
+        // Chunks can arrive in any order, and one that was already received is ignored
+        gateway.receive_member_chunk(&chunk(&nonce, 1, 3, &["2"]));
+        gateway.receive_member_chunk(&chunk(&nonce, 1, 3, &["2"]));
+        gateway.receive_member_chunk(&chunk(&nonce, 0, 3, &["1"]));
+        assert!(poll!(&mut request).is_pending());
+
+        gateway.receive_member_chunk(&chunk(&nonce, 2, 3, &["3"]));
+        let members = request.await.unwrap();
+        assert_eq!(user_ids(&members), vec!["1", "2", "3"]);
+        assert!(gateway.pending_members.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn ignores_chunks_for_other_requests() {
+        let (gateway, sent) = gateway();
+        let request = gateway.request_guild_members(GUILD_ID, MemberFilter::UserIds(vec![Snowflake(1)]), false);
+        tokio::pin!(request);
+        assert!(poll!(&mut request).is_pending());
+        let nonce = sent_nonce(&sent);
+
+        // Chunks without a nonce, or with an unknown one, are for requests that were made somewhere else
+        gateway.receive_member_chunk(&chunk("unknown", 0, 1, &["2"]));
+        let mut no_nonce = chunk(&nonce, 0, 1, &["2"]);
+        no_nonce["nonce"] = Value::Null;
+        gateway.receive_member_chunk(&no_nonce);
+        assert!(poll!(&mut request).is_pending());
+
+        gateway.receive_member_chunk(&chunk(&nonce, 0, 1, &["1"]));
+        assert_eq!(user_ids(&request.await.unwrap()), vec!["1"]);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_the_timeout() {
+        let (gateway, sent) = gateway();
+        let request = gateway.request_guild_members(GUILD_ID, MemberFilter::UserIds(vec![Snowflake(1)]), false);
+        tokio::pin!(request);
+        assert!(poll!(&mut request).is_pending());
+        let nonce = sent_nonce(&sent);
+        gateway.receive_member_chunk(&chunk(&nonce, 0, 2, &["1"]));
+
+        assert!(request.await.is_err());
+        // The request is forgotten, so chunks that arrive late are ignored
+        assert!(gateway.pending_members.lock().unwrap().is_empty());
+        gateway.receive_member_chunk(&chunk(&nonce, 1, 2, &["2"]));
+        assert!(gateway.pending_members.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn fails_without_the_guilds_shard() {
+        let gateway = GatewayHandle::default();
+        let result = gateway
+            .request_guild_members(GUILD_ID, MemberFilter::UserIds(vec![Snowflake(1)]), false)
+            .await;
+        assert!(result.is_err());
+        assert!(gateway.pending_members.lock().unwrap().is_empty());
+    }
+}
@@ -89,3 +89,108 @@ pub enum PayloadOpcode {
      */
     HeartbeatAck = 11,
 }
+
+/**
+ * Gateway Close Event Codes
+ * @docs <https://discord.com/developers/docs/topics/opcodes-and-status-codes#gateway-gateway-close-event-codes>
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u16)]
+pub enum GatewayCloseCode {
+    /// We're not sure what went wrong. Try reconnecting?
+    UnknownError = 4000,
+    /// You sent an invalid Gateway opcode or an invalid payload for an opcode.
+    UnknownOpcode = 4001,
+    /// You sent an invalid payload to Discord.
+    DecodeError = 4002,
+    /// You sent a payload prior to identifying.
+    NotAuthenticated = 4003,
+    /// The account token sent with your identify payload is incorrect.
+    AuthenticationFailed = 4004,
+    /// You sent more than one identify payload.
+    AlreadyAuthenticated = 4005,
+    /// The sequence sent when resuming the session was invalid. Reconnect and start a new session.
+    InvalidSeq = 4007,
+    /// You're sending payloads to Discord too quickly.
+    RateLimited = 4008,
+    /// Your session timed out. Reconnect and start a new one.
+    SessionTimedOut = 4009,
+    /// You sent an invalid shard when identifying.
+    InvalidShard = 4010,
+    /// The session would have handled too many guilds - you are required to shard your connection in order to connect.
+    ShardingRequired = 4011,
+    /// You sent an invalid version for the gateway.
+    InvalidApiVersion = 4012,
+    /// You sent an invalid intent for a Gateway Intent.
+    InvalidIntents = 4013,
+    /// You sent a disallowed intent for a Gateway Intent.
+    DisallowedIntents = 4014,
+}
+
+impl GatewayCloseCode {
+    /// Gets the close code for the code of a close frame. None if it isn't a gateway close code
+    pub fn from_code(code: u16) -> Option<Self> {
+        use GatewayCloseCode::*;
+        Some(match code {
+            4000 => UnknownError,
+            4001 => UnknownOpcode,
+            4002 => DecodeError,
+            4003 => NotAuthenticated,
+            4004 => AuthenticationFailed,
+            4005 => AlreadyAuthenticated,
+            4007 => InvalidSeq,
+            4008 => RateLimited,
+            4009 => SessionTimedOut,
+            4010 => InvalidShard,
+            4011 => ShardingRequired,
+            4012 => InvalidApiVersion,
+            4013 => InvalidIntents,
+            4014 => DisallowedIntents,
+            _ => return None,
+        })
+    }
+
+    /// Whether the bot can connect again after the connection is closed with this code
+    pub fn can_reconnect(&self) -> bool {
+        use GatewayCloseCode::*;
+        !matches!(
+            self,
+            AuthenticationFailed
+                | InvalidShard
+                | ShardingRequired
+                | InvalidApiVersion
+                | InvalidIntents
+                | DisallowedIntents
+        )
+    }
+
+    /// Whether the session can be resumed after the connection is closed with this code. If not, a new session has to be identified
+    pub fn can_resume(&self) -> bool {
+        self.can_reconnect()
+            && !matches!(
+                self,
+                GatewayCloseCode::InvalidSeq | GatewayCloseCode::SessionTimedOut
+            )
+    }
+
+    /// Explains what went wrong, and how it can be fixed
+    pub fn description(&self) -> &'static str {
+        use GatewayCloseCode::*;
+        match self {
+            UnknownError => "Unknown error",
+            UnknownOpcode => "An invalid opcode or payload was sent",
+            DecodeError => "An invalid payload was sent",
+            NotAuthenticated => "A payload was sent before identifying",
+            AuthenticationFailed => "Authentication failed. Check that the bot's token is correct",
+            AlreadyAuthenticated => "More than one identify payload was sent",
+            InvalidSeq => "The sequence number sent when resuming was invalid",
+            RateLimited => "Payloads were sent too quickly",
+            SessionTimedOut => "The session timed out",
+            InvalidShard => "An invalid shard was sent when identifying. Check the shard settings",
+            ShardingRequired => "The bot is in too many guilds for its number of shards. Use more shards",
+            InvalidApiVersion => "An invalid gateway version was used",
+            InvalidIntents => "Invalid intents were sent when identifying",
+            DisallowedIntents => "A privileged intent is not enabled in the developer portal, or the bot isn't approved for it",
+        }
+    }
+}
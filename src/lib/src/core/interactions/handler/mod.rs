@@ -1,11 +1,18 @@
 use crossbeam_channel::Receiver;
 use serde_json::Value;
 
+mod compression;
+pub mod encoding;
+#[cfg(feature = "etf")]
+mod etf;
 pub mod events;
 pub mod gateway;
-mod gateway_payload;
+pub mod gateway_handle;
+pub(crate) mod gateway_payload;
+pub mod session_store;
+pub mod shard_manager;
 pub mod websocket;
 pub trait SocketClient {
-    fn get_command_channel(&self) -> Receiver<(String, Value)>;
+    fn get_command_channel(&self) -> Receiver<(u64, String, Value)>;
     fn send_command(&self, command: String);
 }
@@ -0,0 +1,73 @@
+use std::{collections::HashMap, fs, panic::RefUnwindSafe, path::PathBuf, sync::Mutex};
+
+use crate::util::logger::print_warning;
+
+use super::websocket::GatewaySession;
+
+/// Saves gateway sessions when the bot shuts down, so that they can be resumed when it starts again.
+/// Resuming skips identifying, which doesn't use up the session start limit and doesn't replay every guild.
+/// Sessions are stored by shard as `[shard_id, num_shards]`, so that a session is never resumed with a different shard config.
+pub trait SessionStore: Send + Sync + RefUnwindSafe {
+    /// Loads the session that was saved for a shard
+    fn load(&self, shard: [u64; 2]) -> Option<GatewaySession>;
+    /// Saves the session of a shard
+    fn save(&self, shard: [u64; 2], session: &GatewaySession);
+}
+
+/// Stores gateway sessions in a json file
+pub struct FileSessionStore {
+    /// The file that the sessions are stored in
+    path: PathBuf,
+    /// Stops shards from writing the file at the same time
+    lock: Mutex<()>,
+}
+
+impl FileSessionStore {
+    /// @param path The file that the sessions are stored in. It is created when the first session is saved
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Reads every session in the file, by shard
+    fn read(&self) -> HashMap<String, GatewaySession> {
+        fs::read(&self.path)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes every session to the file, replacing the sessions that were in it
+    fn write(&self, sessions: &HashMap<String, GatewaySession>) {
+        if let Err(e) = fs::write(&self.path, serde_json::to_vec(sessions).unwrap()) {
+            print_warning(
+                "SESSION",
+                format!("Unable to save the sessions to {:?}: {}", self.path, e),
+            );
+        }
+    }
+
+    fn key(shard: [u64; 2]) -> String {
+        format!("{}/{}", shard[0], shard[1])
+    }
+}
+
+impl SessionStore for FileSessionStore {
+    fn load(&self, shard: [u64; 2]) -> Option<GatewaySession> {
+        let _lock = self.lock.lock().unwrap();
+        let mut sessions = self.read();
+        let session = sessions.remove(&FileSessionStore::key(shard))?;
+        // A session can only be resumed once, so it is removed from the file in case the bot stops before saving it again
+        self.write(&sessions);
+        Some(session)
+    }
+
+    fn save(&self, shard: [u64; 2], session: &GatewaySession) {
+        let _lock = self.lock.lock().unwrap();
+        let mut sessions = self.read();
+        sessions.insert(FileSessionStore::key(shard), session.clone());
+        self.write(&sessions);
+    }
+}
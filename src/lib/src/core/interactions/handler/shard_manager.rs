@@ -0,0 +1,330 @@
+use std::{
+    ops::Range,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crossbeam_channel::{bounded, select, unbounded, Receiver, Sender};
+use serde_json::Value;
+
+use crate::{
+    core::abstraction::{context::Context, shutdown::ShutdownHandle},
+    util::{error::Error, logger::print_debug},
+};
+
+use super::{
+    events::IdentifyPayloadData, gateway::get_gateway, gateway_payload::PayloadBase,
+    websocket::WebsocketEventHandler, SocketClient,
+};
+
+/// The time that has to pass between identifies in the same rate limit bucket
+const IDENTIFY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Decides which shards are run by this process
+/// @docs <https://discord.com/developers/docs/topics/gateway#sharding>
+#[derive(Clone, Debug)]
+pub enum ShardConfig {
+    /// Run the number of shards that discord recommends
+    Auto,
+    /// Run every shard out of the given total
+    Total(u64),
+    /// Run only the shards with the given ids out of the given total. Used to split a bot across multiple processes
+    Range {
+        /// The ids of the shards that this process runs
+        ids: Range<u64>,
+        /// The total number of shards across every process
+        total: u64,
+    },
+}
+
+impl Default for ShardConfig {
+    fn default() -> Self {
+        ShardConfig::Auto
+    }
+}
+
+impl ShardConfig {
+    /**
+     * Works out which shards to run, and checks that discord would accept them
+     * @param recommended The number of shards that discord recommends
+     * @return The total number of shards, and the ids of the shards that this process runs
+     */
+    pub(crate) fn resolve(&self, recommended: u64) -> Result<(u64, Range<u64>), Error> {
+        let (total, ids) = match self.clone() {
+            ShardConfig::Auto => (recommended, 0..recommended),
+            ShardConfig::Total(total) => (total, 0..total),
+            ShardConfig::Range { ids, total } => (total, ids),
+        };
+        if ids.is_empty() || ids.end > total {
+            return Err(Error::Gateway(format!(
+                "Invalid shard config: shards {:?} of {} (ids have to be a non-empty range below the total)",
+                ids, total
+            )));
+        }
+        Ok((total, ids))
+    }
+}
+
+/// Makes sure that shards identify in the order that discord allows.
+/// Shards are put into `max_concurrency` buckets by `shard_id % max_concurrency`,
+/// and only one shard in each bucket can identify every 5 seconds.
+/// @docs <https://discord.com/developers/docs/topics/gateway#session-start-limit-object-max-concurrency>
+pub struct IdentifyLimiter {
+    /// The time between identifies in the same bucket
+    interval: Duration,
+    /// A channel for each bucket that holds the time of the bucket's last identify while no shard is waiting to identify in it
+    buckets: Vec<IdentifyBucket>,
+}
+
+/// Both ends of a bucket's channel
+type IdentifyBucket = (Sender<Option<Instant>>, Receiver<Option<Instant>>);
+
+/// Lets a shard identify. The bucket is given back to the next shard once this is dropped,
+/// and the wait only starts again if an identify was sent with it
+pub struct IdentifyPermit {
+    /// Where the bucket is given back
+    bucket: Sender<Option<Instant>>,
+    /// The time of the bucket's last identify
+    last_identify: Option<Instant>,
+}
+
+impl IdentifyLimiter {
+    pub fn new(max_concurrency: u64) -> Self {
+        Self::with_interval(max_concurrency, IDENTIFY_INTERVAL)
+    }
+
+    /**
+     * @param max_concurrency The number of identify requests allowed per interval
+     * @param interval The time between identifies in the same bucket
+     */
+    pub(crate) fn with_interval(max_concurrency: u64, interval: Duration) -> Self {
+        let buckets = (0..max_concurrency.max(1))
+            .map(|_| {
+                let (s, r) = bounded(1);
+                s.send(None).unwrap();
+                (s, r)
+            })
+            .collect();
+        Self { interval, buckets }
+    }
+
+    /**
+     * Blocks the thread until the given shard is allowed to identify, or until the bot shuts down.
+     * Only one shard in a bucket holds a permit at a time, so the shard should connect and identify before dropping it
+     * @return The permit to identify. None if the bot is shutting down
+     */
+    pub fn wait(&self, shard_id: u64, shutdown: &ShutdownHandle) -> Option<IdentifyPermit> {
+        let (bucket, turn) = &self.buckets[(shard_id % self.buckets.len() as u64) as usize];
+        let signal = shutdown.signal();
+        let last_identify = select! {
+            recv(turn) -> last_identify => last_identify.unwrap(),
+            recv(signal) -> _ => return None,
+        };
+        // Made before waiting, so that the bucket is given back if the bot shuts down
+        let permit = IdentifyPermit {
+            bucket: bucket.clone(),
+            last_identify,
+        };
+        if let Some(last_identify) = last_identify {
+            let identify_at = last_identify + self.interval;
+            if shutdown.wait_timeout(identify_at.saturating_duration_since(Instant::now())) {
+                return None;
+            }
+        }
+        Some(permit)
+    }
+}
+
+impl IdentifyPermit {
+    /// Records that the identify was sent, so the next shard in the bucket waits for the interval
+    pub fn identified(mut self) {
+        self.last_identify = Some(Instant::now());
+    }
+}
+
+impl Drop for IdentifyPermit {
+    fn drop(&mut self) {
+        self.bucket.send(self.last_identify).ok();
+    }
+}
+
+/// Runs a connection to the gateway for every shard, and combines their events into a single channel.
+/// Every event is tagged with the id of the shard that received it.
+pub struct ShardManager {
+    /// The connection for each shard
+    shards: Vec<WebsocketEventHandler>,
+    /// Events from every shard as `(shard_id, event_name, data)`
+    event_receiver: Receiver<(u64, String, Value)>,
+    /// The total number of shards across every process
+    total_shards: u64,
+}
+
+impl ShardManager {
+    /**
+     * Starts every shard that is selected by the shard config in the context's settings
+     * @param identify The identify payload, which will have the shard information added to it for each shard
+     * @return An error if the gateway can't be fetched, or if the shard config selects shards that don't exist
+     */
+    pub async fn create(ctx: Context, identify: IdentifyPayloadData) -> Result<ShardManager, Error> {
+        // TODO so the gateway says that it shouldn't be cached. WHAT DOES THIS MEAN????
+        // does it mean not cached between instances, and having it get a new gateway on startup?
+        // or does it want use to periodically get a new gateway while the bot is running? plz help
+        let gateway = get_gateway(ctx.clone()).await?;
+
+        let (total_shards, ids) = ctx.settings.shards.resolve(gateway.shards)?;
+
+        if ctx.settings.debug {
+            print_debug(
+                "SHARDS",
+                format!(
+                    "Starting shards {:?} of {} (max concurrency: {})",
+                    ids, total_shards, gateway.session_start_limit.max_concurrency
+                ),
+            );
+        }
+
+        let limiter = Arc::new(IdentifyLimiter::new(
+            gateway.session_start_limit.max_concurrency,
+        ));
+        let (s, r) = unbounded();
+
+        let shards = ids
+            .map(|shard_id| {
+                let identify = PayloadBase::new(IdentifyPayloadData {
+                    shard: Some([shard_id, total_shards]),
+                    ..identify.clone()
+                });
+
+                WebsocketEventHandler::create(
+                    ctx.clone(),
+                    [shard_id, total_shards],
+                    gateway.url.clone(),
+                    serde_json::to_string(&identify).unwrap(),
+                    limiter.clone(),
+                    s.clone(),
+                    r.clone(),
+                )
+            })
+            .collect();
+
+        Ok(ShardManager {
+            shards,
+            event_receiver: r,
+            total_shards,
+        })
+    }
+
+    /// Gets the total number of shards across every process
+    pub fn total_shards(&self) -> u64 {
+        self.total_shards
+    }
+
+    /// Gets the connection for each shard that is run by this process
+    pub fn shards(&self) -> &Vec<WebsocketEventHandler> {
+        &self.shards
+    }
+}
+
+impl SocketClient for ShardManager {
+    fn get_command_channel(&self) -> Receiver<(u64, String, Value)> {
+        self.event_receiver.clone()
+    }
+
+    // Sends a command through every shard
+    fn send_command(&self, command: String) {
+        for shard in &self.shards {
+            shard.send_command(command.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use crate::core::abstraction::shutdown::ShutdownHandle;
+
+    use super::{IdentifyLimiter, ShardConfig};
+
+    const INTERVAL: Duration = Duration::from_millis(200);
+
+    #[test]
+    fn resolves_shard_configs() {
+        assert_eq!(ShardConfig::Auto.resolve(4).unwrap(), (4, 0..4));
+        assert_eq!(ShardConfig::Total(2).resolve(4).unwrap(), (2, 0..2));
+        assert_eq!(ShardConfig::Range { ids: 2..4, total: 4 }.resolve(1).unwrap(), (4, 2..4));
+    }
+
+    #[test]
+    fn rejects_shards_that_dont_exist() {
+        assert!(ShardConfig::Total(0).resolve(4).is_err());
+        assert!(ShardConfig::Range { ids: 2..2, total: 4 }.resolve(4).is_err());
+        assert!(ShardConfig::Range { ids: 2..5, total: 4 }.resolve(4).is_err());
+    }
+
+    #[test]
+    fn spaces_identifies_in_the_same_bucket() {
+        let limiter = IdentifyLimiter::with_interval(2, INTERVAL);
+        let shutdown = ShutdownHandle::default();
+        let start = Instant::now();
+
+        // Shards 0 and 1 are in different buckets, so they can identify at the same time
+        limiter.wait(0, &shutdown).unwrap().identified();
+        limiter.wait(1, &shutdown).unwrap().identified();
+        assert!(start.elapsed() < INTERVAL);
+
+        // Shard 2 is in the same bucket as shard 0 (2 % 2 == 0)
+        limiter.wait(2, &shutdown).unwrap().identified();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= INTERVAL && elapsed < INTERVAL * 2, "{:?}", elapsed);
+    }
+
+    #[test]
+    fn only_identifies_use_up_the_interval() {
+        let limiter = IdentifyLimiter::with_interval(1, INTERVAL);
+        let shutdown = ShutdownHandle::default();
+        let start = Instant::now();
+
+        // The connection failed before the identify was sent, so the next shard doesn't have to wait
+        drop(limiter.wait(0, &shutdown).unwrap());
+        limiter.wait(1, &shutdown).unwrap();
+        assert!(start.elapsed() < INTERVAL);
+    }
+
+    #[test]
+    fn waits_for_the_shard_that_holds_the_bucket() {
+        let limiter = Arc::new(IdentifyLimiter::with_interval(1, INTERVAL));
+        let shutdown = ShutdownHandle::default();
+        let start = Instant::now();
+
+        let permit = limiter.wait(0, &shutdown).unwrap();
+        let waiting = {
+            let (limiter, shutdown) = (limiter.clone(), shutdown.clone());
+            thread::spawn(move || {
+                limiter.wait(1, &shutdown).unwrap().identified();
+                start.elapsed()
+            })
+        };
+        // Connecting takes a while, and the next shard waits the interval from when the identify is sent
+        thread::sleep(INTERVAL / 2);
+        permit.identified();
+        let elapsed = waiting.join().unwrap();
+        assert!(elapsed >= INTERVAL + INTERVAL / 2, "{:?}", elapsed);
+    }
+
+    #[test]
+    fn stops_waiting_on_shutdown() {
+        let limiter = IdentifyLimiter::with_interval(1, Duration::from_secs(60));
+        let shutdown = ShutdownHandle::default();
+        limiter.wait(0, &shutdown).unwrap().identified();
+
+        shutdown.shutdown();
+        let start = Instant::now();
+        assert!(limiter.wait(1, &shutdown).is_none());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -1,17 +1,29 @@
 use std::{
     sync::{Arc, Mutex},
     thread,
+    time::{Duration, Instant},
 };
 
-use crate::core::{
-    abstraction::context::Context,
-    interactions::handler::{events::core::HelloPayloadData, gateway_payload::PayloadBase},
+use crate::{
+    core::{
+        abstraction::context::Context,
+        interactions::handler::{events::core::HelloPayloadData, gateway_payload::PayloadBase},
+    },
+    util::{
+        error::Error,
+        logger::print_debug,
+    },
 };
 
 use super::{
-    events::core::HeartBeatPayloadData,
-    gateway::{get_gateway, Gateway},
-    gateway_payload::PayloadOpcode,
+    compression::{InflateError, ZlibStream},
+    encoding::GatewayEncoding,
+    events::{
+        core::{HeartBeatPayloadData, InvalidSessionPayloadData, ResumePayloadData},
+        lifecycle_payloads::{ShardConnecting, ShardDisconnected, ShardReady, ShardResumed},
+    },
+    gateway_payload::{GatewayCloseCode, PayloadOpcode},
+    shard_manager::IdentifyLimiter,
     SocketClient,
 };
 use async_std::task::block_on;
@@ -21,101 +33,543 @@ use futures_util::{
     stream::{SplitSink, SplitStream, StreamExt},
     SinkExt,
 };
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use tokio::net::TcpStream;
-use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tokio::{net::TcpStream, sync::Notify};
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{
+        protocol::{frame::coding::CloseCode, CloseFrame},
+        Message,
+    },
+    MaybeTlsStream, WebSocketStream,
+};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// The shortest time to wait before trying to reconnect to the gateway
+const MIN_RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+/// The longest time to wait before trying to reconnect to the gateway
+const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Information about the gateway session that is kept between connections so that it can be resumed
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct GatewaySession {
+    /// The id of the session, received in the Ready event
+    pub session_id: Option<String>,
+    /// The url that should be used when resuming the session
+    pub resume_gateway_url: Option<String>,
+    /// The last sequence number that was received. See <https://discord.com/developers/docs/topics/gateway#heartbeat>
+    pub sequence_num: HeartBeatPayloadData,
+}
+
+impl GatewaySession {
+    /// Forgets the session so that the next connection will identify instead of resuming
+    pub fn reset(&mut self) {
+        *self = GatewaySession::default();
+    }
+}
+
+/// The reason that a connection to the gateway ended
+enum Disconnect {
+    /// Discord asked the client to reconnect and resume
+    Reconnect,
+    /// Discord invalidated the session. The inner value is true if the session can be resumed
+    InvalidSession(InvalidSessionPayloadData),
+    /// The connection was closed or dropped. Holds the close code if the connection was closed with one
+    ConnectionLost(Option<u16>),
+    /// A heartbeat wasn't acknowledged, so the connection is assumed to be dead
+    Zombie,
+    /// Discord closed the connection with a gateway close code
+    Closed(GatewayCloseCode),
+    /// The bot is shutting down
+    Shutdown,
+    /// A compressed message couldn't be decompressed, so nothing else on the connection can be read
+    Decompress(InflateError),
+}
+
+/// Sends heartbeats for a single connection, and keeps track of whether they are acknowledged
+struct Heartbeater {
+    /// Used to send heartbeats to the websocket
+    socket_send: Sender<Message>,
+    /// The session that the sequence number is taken from
+    session: Arc<Mutex<GatewaySession>>,
+    /// When the last heartbeat was sent, and whether it has been acknowledged
+    last_sent: Mutex<Option<(Instant, bool)>>,
+    /// Notified when a heartbeat isn't acknowledged before the next one is due
+    zombie: Notify,
+}
+
+impl Heartbeater {
+    fn new(socket_send: Sender<Message>, session: Arc<Mutex<GatewaySession>>) -> Self {
+        Self {
+            socket_send,
+            session,
+            last_sent: Mutex::new(None),
+            zombie: Notify::new(),
+        }
+    }
+
+    /// Sends a heartbeat with the last sequence number. Returns false if the connection is closed
+    fn beat(&self) -> bool {
+        let seq = self.session.lock().unwrap().sequence_num;
+        let heartbeat = Message::Text(serde_json::to_string(&PayloadBase::new(seq)).unwrap());
+        *self.last_sent.lock().unwrap() = Some((Instant::now(), false));
+        self.socket_send.send(heartbeat).is_ok()
+    }
+
+    /// Marks the last heartbeat as acknowledged, and returns the time that it took to be acknowledged
+    fn ack(&self) -> Option<Duration> {
+        let mut last_sent = self.last_sent.lock().unwrap();
+        let (sent_at, acked) = last_sent.as_mut()?;
+        *acked = true;
+        Some(sent_at.elapsed())
+    }
+
+    /// Whether the last heartbeat has been acknowledged. True if no heartbeat has been sent yet
+    fn is_acked(&self) -> bool {
+        self.last_sent
+            .lock()
+            .unwrap()
+            .map_or(true, |(_, acked)| acked)
+    }
+}
 
 pub struct WebsocketEventHandler {
-    event_receiver: Receiver<(String, Value)>,
+    event_receiver: Receiver<(u64, String, Value)>,
     command_sender: Sender<Message>,
+    /// The shard that this connection is for, as `[shard_id, num_shards]`
+    shard: [u64; 2],
 }
 
 impl WebsocketEventHandler {
-    pub async fn create(ctx: Context) -> WebsocketEventHandler {
-        let (s, r) = unbounded();
+    /**
+     * Connects a shard to the gateway, and keeps the connection alive until the bot is closed.
+     * If the connection drops, it will be resumed (or re-identified if it can't be resumed) on a new connection.
+     * @param shard The shard to connect as `[shard_id, num_shards]`
+     * @param gateway_url The url of the gateway
+     * @param identify The identify payload that is sent when a new session is started
+     * @param limiter Makes sure that identifies are sent in the order that discord allows
+     * @param event_output The channel that received events are sent to
+     * @param event_receiver The receiving end of event_output
+     */
+    pub fn create(
+        ctx: Context,
+        shard: [u64; 2],
+        gateway_url: String,
+        identify: String,
+        limiter: Arc<IdentifyLimiter>,
+        event_output: Sender<(u64, String, Value)>,
+        event_receiver: Receiver<(u64, String, Value)>,
+    ) -> WebsocketEventHandler {
         let (s2, r2) = unbounded();
+        // Lets commands be sent to this shard from the context
+        ctx.gateway.add_shard(shard, s2.clone());
 
         let handler = WebsocketEventHandler {
-            event_receiver: r,
+            event_receiver,
             command_sender: s2,
+            shard,
         };
-        async {
-            // TODO so the gateway says that it shouldn't be cached. WHAT DOES THIS MEAN????
-            // does it mean not cached between instances, and having it get a new gateway on startup?
-            // or does it want use to periodically get a new gateway while the bot is running? plz help
-            let gateway = get_gateway(ctx).await.unwrap();
-            thread::Builder::new()
-                .name("Websocket_Interaction_Handler".to_string())
-                .spawn(move || block_on(WebsocketEventHandler::run(s, r2, gateway)))
-                .unwrap()
-        }
-        .await;
+
+        thread::Builder::new()
+            .name(format!("Websocket_Interaction_Handler_{}", shard[0]))
+            .spawn(move || {
+                block_on(WebsocketEventHandler::run(
+                    ctx,
+                    shard,
+                    event_output,
+                    r2,
+                    gateway_url,
+                    identify,
+                    limiter,
+                ))
+            })
+            .unwrap();
 
         handler
     }
 
+    /// Gets the shard that this connection is for, as `[shard_id, num_shards]`
+    pub fn get_shard(&self) -> [u64; 2] {
+        self.shard
+    }
+
+    /// Keeps a connection to the gateway open, reconnecting and resuming whenever it is lost.
+    /// Stops if the gateway closes the connection with a code that can't be recovered from
     async fn run(
-        event_output: Sender<(String, Value)>,
+        ctx: Context,
+        shard: [u64; 2],
+        event_output: Sender<(u64, String, Value)>,
         incoming_commands: Receiver<Message>,
-        gateway: Gateway,
+        gateway_url: String,
+        identify: String,
+        limiter: Arc<IdentifyLimiter>,
     ) {
-        // Url of the websocket
-        let url = url::Url::parse(&format!("{}/?v=9&encoding=json", gateway.url)).unwrap();
-        // println!("Connecting to {}", url);
+        let shard_id = shard[0];
+        // Resume the session that was saved when the bot last shut down, if there is one
+        let stored_session = ctx
+            .settings
+            .session_store
+            .as_ref()
+            .and_then(|store| store.load(shard));
+        let session = Arc::new(Mutex::new(stored_session.unwrap_or_default()));
+        let mut backoff = MIN_RECONNECT_BACKOFF;
+        // Whether a connection has been attempted before, so that the following ones are reported as reconnects
+        let mut reconnecting = false;
+
+        // Every way out of the loop goes past the end of it, so that the session is always saved
+        loop {
+            if ctx.shutdown.is_requested() {
+                break;
+            }
 
-        // Connect to the websocket
-        let (mut socket, _) = connect_async(url).await.expect("Can't connect");
-        // println!("{}", response.status());
+            let (session_id, resume_url, seq) = {
+                let session = session.lock().unwrap();
+                (
+                    session.session_id.clone(),
+                    session.resume_gateway_url.clone(),
+                    session.sequence_num,
+                )
+            };
+
+            // Sessions should be resumed on the url that was given in the ready event
+            let base_url = resume_url.unwrap_or_else(|| gateway_url.clone());
+            // Url of the websocket
+            let compression = if ctx.settings.compression {
+                "&compress=zlib-stream"
+            } else {
+                ""
+            };
+            let encoding = ctx.settings.encoding;
+            let url = url::Url::parse(&format!(
+                "{}/?v=9&encoding={}{}",
+                base_url,
+                encoding.name(),
+                compression
+            ))
+            .unwrap();
+
+            // Every connection gets a new inflate context
+            let mut inflater = if ctx.settings.compression {
+                Some(ZlibStream::new())
+            } else {
+                None
+            };
+
+            WebsocketEventHandler::emit(
+                &event_output,
+                shard_id,
+                if reconnecting {
+                    "SHARD_RECONNECTING"
+                } else {
+                    "SHARD_CONNECTING"
+                },
+                ShardConnecting {
+                    shard_id,
+                    resuming: session_id.is_some(),
+                },
+            );
+            reconnecting = true;
+
+            // A new session waits for its turn to identify before connecting, because the connection isn't heartbeating until after the identify is sent
+            let permit = match session_id {
+                Some(_) => None,
+                None => match limiter.wait(shard_id, &ctx.shutdown) {
+                    Some(permit) => Some(permit),
+                    None => break,
+                },
+            };
+
+            // Connect to the websocket
+            let (mut socket, hello_payload) =
+                match WebsocketEventHandler::connect(url, encoding, &mut inflater).await {
+                    Some(connection) => connection,
+                    None => {
+                        // Other shards in the bucket can identify while this one waits
+                        drop(permit);
+                        if ctx.settings.debug {
+                            print_debug(
+                                "GATEWAY",
+                                format!("Unable to connect. Retrying in {:?}", backoff),
+                            );
+                        }
+                        WebsocketEventHandler::sleep(&ctx, backoff).await;
+                        backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+                        continue;
+                    }
+                };
 
-        // Receive the hello message from the websocket and then parse it
-        let mut hello_msg = socket.next().await.unwrap().unwrap().into_data();
+            // Resume the previous session if there is one, otherwise start a new one
+            let start_cmd = match session_id {
+                Some(session_id) => {
+                    if ctx.settings.debug {
+                        print_debug("GATEWAY", format!("Resuming session {}", session_id));
+                    }
+                    serde_json::to_string(&PayloadBase::new(ResumePayloadData {
+                        token: ctx.token.clone(),
+                        session_id,
+                        seq,
+                    }))
+                    .unwrap()
+                }
+                None => {
+                    if ctx.settings.debug {
+                        print_debug("GATEWAY", format!("Identifying shard {}", shard_id));
+                    }
+                    identify.clone()
+                }
+            };
+            // This is sent before anything else so that no commands reach the gateway before the session is established
+            if socket
+                .send(encoding.encode(Message::Text(start_cmd)))
+                .await
+                .is_err()
+            {
+                // The identify wasn't sent, so other shards in the bucket can identify while this one waits
+                drop(permit);
+                WebsocketEventHandler::emit(
+                    &event_output,
+                    shard_id,
+                    "SHARD_DISCONNECTED",
+                    ShardDisconnected {
+                        shard_id,
+                        close_code: None,
+                        will_reconnect: true,
+                    },
+                );
+                if ctx.settings.debug {
+                    print_debug(
+                        "GATEWAY",
+                        format!("Connection lost before the session started. Reconnecting in {:?}", backoff),
+                    );
+                }
+                WebsocketEventHandler::sleep(&ctx, backoff).await;
+                backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+                continue;
+            }
+            // The next shard in the bucket waits from when this identify was sent
+            if let Some(permit) = permit {
+                permit.identified();
+            }
 
-        let hello_payload: PayloadBase<HelloPayloadData> =
-            serde_json::from_slice(&mut *hello_msg).unwrap();
+            // Split the socket so that different threads can handle different parts of the websocket
+            let (socket_sink, socket_recv) = socket.split();
+
+            // Used to send messages to the websocket
+            let (heartbeat_send, heartbeat_receiver) = unbounded();
+
+            // This will send requests to the websocket that are sent through the incoming_commands channel and the heartbeat_receiver channel
+            let commands = incoming_commands.clone();
+            let sender = thread::spawn(move || {
+                block_on(WebsocketEventHandler::sender(
+                    socket_sink,
+                    encoding,
+                    commands,
+                    heartbeat_receiver,
+                ))
+            });
+
+            let heartbeater = Arc::new(Heartbeater::new(heartbeat_send.clone(), session.clone()));
+            let heartbeater_cp = heartbeater.clone();
+
+            // Heartbeat loop
+            thread::spawn(move || {
+                block_on(WebsocketEventHandler::heartbeat_loop(
+                    heartbeater_cp,
+                    hello_payload.heartbeat_interval,
+                ))
+            });
+
+            // Listen for events, and then send them when they are available
+            let reason = WebsocketEventHandler::event_receiver(
+                &ctx,
+                shard_id,
+                &event_output,
+                socket_recv,
+                encoding,
+                inflater,
+                heartbeater,
+                session.clone(),
+                &mut backoff,
+            )
+            .await;
+
+            let (close_code, will_reconnect) = match reason {
+                Disconnect::Closed(code) => (Some(code as u16), code.can_reconnect()),
+                Disconnect::ConnectionLost(code) => (code, true),
+                Disconnect::Shutdown => (None, false),
+                _ => (None, true),
+            };
+            WebsocketEventHandler::emit(
+                &event_output,
+                shard_id,
+                "SHARD_DISCONNECTED",
+                ShardDisconnected {
+                    shard_id,
+                    close_code,
+                    will_reconnect,
+                },
+            );
+
+            // Close the connection so that the sender and heartbeat threads stop.
+            // A normal close ends the session, so it is only used when shutting down without a session store
+            let close_frame = match reason {
+                Disconnect::Shutdown => Some(CloseFrame {
+                    code: match ctx.settings.session_store {
+                        Some(_) => CloseCode::Restart,
+                        None => CloseCode::Normal,
+                    },
+                    reason: "Shutting down".into(),
+                }),
+                _ => None,
+            };
+            heartbeat_send.send(Message::Close(close_frame)).ok();
+
+            match reason {
+                Disconnect::Shutdown => {
+                    // Wait for the close frame to be sent
+                    sender.join().ok();
+                    if ctx.settings.debug {
+                        print_debug("GATEWAY", format!("Shard {} closed", shard_id));
+                    }
+                    break;
+                }
+                Disconnect::Reconnect => {
+                    if ctx.settings.debug {
+                        print_debug("GATEWAY", "Reconnect requested".to_string());
+                    }
+                }
+                Disconnect::InvalidSession(resumable) => {
+                    if ctx.settings.debug {
+                        print_debug(
+                            "GATEWAY",
+                            format!("Session invalidated (resumable: {})", resumable),
+                        );
+                    }
+                    if !resumable {
+                        session.lock().unwrap().reset();
+                        // Discord asks that clients wait a random amount of time between 1 and 5 seconds before identifying again
+                        let wait = rand::thread_rng().gen_range(1000..=5000);
+                        WebsocketEventHandler::sleep(&ctx, Duration::from_millis(wait)).await;
+                    }
+                }
+                Disconnect::ConnectionLost(_) => {
+                    if ctx.settings.debug {
+                        print_debug(
+                            "GATEWAY",
+                            format!("Connection lost. Reconnecting in {:?}", backoff),
+                        );
+                    }
+                    WebsocketEventHandler::sleep(&ctx, backoff).await;
+                    backoff = (backoff * 2).min(MAX_RECONNECT_BACKOFF);
+                    continue;
+                }
+                Disconnect::Closed(code) => {
+                    if !code.can_reconnect() {
+                        // Reconnecting would fail in the same way, so the error is passed to the bot instead
+                        ctx.gateway.report_fatal(Error::Gateway(format!(
+                            "Shard {} was closed by the gateway with code {} ({:?}): {}",
+                            shard_id,
+                            code as u16,
+                            code,
+                            code.description()
+                        )));
+                        // The session was ended by the gateway, so it can't be resumed the next time the bot starts
+                        session.lock().unwrap().reset();
+                        break;
+                    }
+                    if ctx.settings.debug {
+                        print_debug(
+                            "GATEWAY",
+                            format!(
+                                "Closed with code {} ({:?}). Reconnecting",
+                                code as u16, code
+                            ),
+                        );
+                    }
+                    if !code.can_resume() {
+                        session.lock().unwrap().reset();
+                    }
+                }
+                Disconnect::Zombie => {
+                    if ctx.settings.debug {
+                        print_debug(
+                            "GATEWAY",
+                            "Heartbeat wasn't acknowledged. Reconnecting".to_string(),
+                        );
+                    }
+                }
+                Disconnect::Decompress(error) => {
+                    if ctx.settings.debug {
+                        print_debug(
+                            "GATEWAY",
+                            format!("Unable to decompress a message ({}). Reconnecting", error),
+                        );
+                    }
+                }
+            }
+            backoff = MIN_RECONNECT_BACKOFF;
+        }
 
-        // Split the socket so that different threads can handle different parts of the websocket
-        let (socket_sink, socket_recv) = socket.split();
+        if let Some(store) = &ctx.settings.session_store {
+            store.save(shard, &session.lock().unwrap());
+        }
+    }
 
-        // Used to send messages to the websocket
-        let (heartbeat_send, heartbeat_receiver) = unbounded();
+    /// Waits for the given time, or until the bot shuts down
+    async fn sleep(ctx: &Context, duration: Duration) {
+        async_std::future::timeout(duration, ctx.shutdown.wait())
+            .await
+            .ok();
+    }
 
-        // The Sequence number. See https://discord.com/developers/docs/topics/gateway#heartbeat
-        let sequence_num = Arc::new(Mutex::new(None));
+    /// Connects to the websocket and waits for the hello message. Returns None if the connection fails
+    async fn connect(
+        url: url::Url,
+        encoding: GatewayEncoding,
+        inflater: &mut Option<ZlibStream>,
+    ) -> Option<(Socket, HelloPayloadData)> {
+        let (mut socket, _) = connect_async(url).await.ok()?;
 
-        // This will send requests to the websocket that are sent through the incoming_commands channel and the heartbeat_receiver channel
-        thread::spawn(move || {
-            block_on(WebsocketEventHandler::sender(
-                socket_sink,
-                incoming_commands,
-                heartbeat_receiver,
-            ))
-        });
+        // Receive the hello message from the websocket and then parse it
+        let hello_msg = loop {
+            let message = socket.next().await?.ok()?;
+            if let Some(payload) = WebsocketEventHandler::payload_bytes(message, inflater).ok()? {
+                break payload;
+            }
+        };
 
-        let heatbeat_send1 = heartbeat_send.clone();
-        let seq_num_cp = sequence_num.clone();
+        let hello_payload: PayloadBase<HelloPayloadData> = encoding.decode(&hello_msg)?;
 
-        // Heartbeat loop
-        thread::spawn(move || {
-            block_on(WebsocketEventHandler::heartbeat_loop(
-                heatbeat_send1,
-                hello_payload.data.heartbeat_interval,
-                seq_num_cp,
-            ))
-        });
+        Some((socket, hello_payload.data))
+    }
 
-        // Listen for events, and then send them when they are available
-        WebsocketEventHandler::event_receiver(
-            event_output,
-            socket_recv,
-            heartbeat_send,
-            sequence_num,
-        )
-        .await;
+    /**
+     * Gets the raw payload out of a message, and decompresses it if the connection is compressed.
+     * @return None if the message doesn't contain a payload, or if it is only part of a compressed payload
+     */
+    fn payload_bytes(
+        message: Message,
+        inflater: &mut Option<ZlibStream>,
+    ) -> Result<Option<Vec<u8>>, InflateError> {
+        match message {
+            Message::Text(text) => Ok(Some(text.into_bytes())),
+            Message::Binary(data) => match inflater {
+                Some(inflater) => inflater.push(&data),
+                None => Ok(Some(data)),
+            },
+            _ => Ok(None),
+        }
     }
 
-    /// This will send requests to the websocket that are sent through the incoming_commands channel and the heartbeat_receiver channel
+    /// This will send requests to the websocket that are sent through the incoming_commands channel and the heartbeat_receiver channel.
+    /// Sending a close message through the heartbeat channel will close the connection and stop the sender.
+    /// Commands are written as json, and are converted to the connection's encoding before they are sent.
     async fn sender(
-        mut socket_send: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>,
+        mut socket_send: SplitSink<Socket, Message>,
+        encoding: GatewayEncoding,
         to_send: Receiver<Message>,
         to_send_heartbeat: Receiver<Message>,
     ) {
@@ -140,24 +594,40 @@ impl WebsocketEventHandler {
             // Block the thread until there is a message to send
             sel.ready();
 
-            // Send the heartbeat if there is allowance to do so, and if there is a heartbeat message to send
-            while let Ok(msg) = to_send_heartbeat.try_recv() {
-                if allowance <= 1.0 {
-                    break;
+            // Send the heartbeat if there is allowance to do so, and if there is a heartbeat message to send.
+            // The allowance is checked first so that messages are left in the channel until they can be sent
+            while allowance > 1.0 {
+                let msg = match to_send_heartbeat.try_recv() {
+                    Ok(msg) => msg,
+                    Err(_) => break,
+                };
+                if let Message::Close(_) = msg {
+                    socket_send.send(msg).await.ok();
+                    return;
+                }
+                if socket_send.send(encoding.encode(msg)).await.is_err() {
+                    return;
                 }
-                socket_send.send(msg).await.unwrap();
                 allowance -= 1.0;
             }
 
             // Send the message if there is allowance to do so, and if there is a message to send
-            while let Ok(msg) = to_send.try_recv() {
-                if allowance <= 1.0 {
-                    break;
+            while allowance > 1.0 {
+                let msg = match to_send.try_recv() {
+                    Ok(msg) => msg,
+                    Err(_) => break,
+                };
+                if socket_send.send(encoding.encode(msg)).await.is_err() {
+                    return;
                 }
-                socket_send.send(msg).await.unwrap();
                 allowance -= 1.0;
             }
 
+            // Wait for the allowance to refill instead of spinning on the messages that are still waiting
+            if allowance <= 1.0 {
+                thread::sleep(Duration::from_secs_f64(1.0 / allowance_rate));
+            }
+
             // Calculate the new allowance
             allowance += start.elapsed().as_secs_f64() * allowance_rate;
             if allowance > max_allowance {
@@ -166,63 +636,158 @@ impl WebsocketEventHandler {
         }
     }
 
-    /// Sends a heartbeat to the websocket every `heartbeat_interval` seconds
-    async fn heartbeat_loop(
-        socket_send: Sender<Message>,
-        heartbeat_interval: u64,
-        sequence_num: Arc<Mutex<HeartBeatPayloadData>>,
-    ) {
+    /// Sends a heartbeat to the websocket every `heartbeat_interval` milliseconds until the connection is closed.
+    /// If a heartbeat hasn't been acknowledged by the time the next one is due, the connection is reported as a zombie.
+    /// @docs <https://discord.com/developers/docs/topics/gateway#sending-heartbeats>
+    async fn heartbeat_loop(heartbeater: Arc<Heartbeater>, heartbeat_interval: u64) {
+        // The first heartbeat is sent after a random fraction of the interval, so that shards that connect together don't heartbeat together
+        let jitter = rand::thread_rng().gen::<f64>();
+        thread::sleep(Duration::from_millis(heartbeat_interval).mul_f64(jitter));
+
         loop {
-            let seq = *sequence_num.lock().unwrap();
-            let heartbeat = Message::Text(serde_json::to_string(&PayloadBase::new(seq)).unwrap());
-            socket_send.send(heartbeat).unwrap();
-            thread::sleep(std::time::Duration::from_millis(heartbeat_interval));
+            if !heartbeater.is_acked() {
+                heartbeater.zombie.notify_one();
+                break;
+            }
+            if !heartbeater.beat() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(heartbeat_interval));
         }
     }
 
-    /// Will receive events from the websocket and send them to the event_output channel
+    /// Will receive events from the websocket and send them to the event_output channel until the connection ends.
+    /// The reconnect backoff is reset once the session is established
+    #[allow(clippy::too_many_arguments)]
     async fn event_receiver(
-        events: Sender<(String, Value)>,
-        mut socket_recv: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
-        socket_send: Sender<Message>,
-        sequence_num: Arc<Mutex<HeartBeatPayloadData>>,
-    ) {
-        // Listen for the socket to receive a message
-        while let Ok(message) = socket_recv.next().await.unwrap() {
+        ctx: &Context,
+        shard_id: u64,
+        events: &Sender<(u64, String, Value)>,
+        mut socket_recv: SplitStream<Socket>,
+        encoding: GatewayEncoding,
+        mut inflater: Option<ZlibStream>,
+        heartbeater: Arc<Heartbeater>,
+        session: Arc<Mutex<GatewaySession>>,
+        backoff: &mut Duration,
+    ) -> Disconnect {
+        loop {
+            // Listen for the socket to receive a message, unless the heartbeat loop finds that the connection is dead
+            let message = tokio::select! {
+                message = socket_recv.next() => message,
+                _ = heartbeater.zombie.notified() => return Disconnect::Zombie,
+                _ = ctx.shutdown.wait() => return Disconnect::Shutdown,
+            };
+            let message = match message {
+                Some(Ok(message)) => message,
+                _ => break,
+            };
+            if let Message::Close(frame) = message {
+                return match frame
+                    .as_ref()
+                    .and_then(|f| GatewayCloseCode::from_code(f.code.into()))
+                {
+                    Some(code) => Disconnect::Closed(code),
+                    None => Disconnect::ConnectionLost(frame.map(|f| f.code.into())),
+                };
+            }
+            let data = match WebsocketEventHandler::payload_bytes(message, &mut inflater) {
+                Ok(Some(data)) => data,
+                Ok(None) => continue,
+                // The inflate context is broken, so the connection is restarted with a new one
+                Err(error) => return Disconnect::Decompress(error),
+            };
+
             // Parse the payload
-            let payload: PayloadBase<Value> =
-                serde_json::from_slice(&*message.into_data()).unwrap();
+            let payload: PayloadBase<Value> = match encoding.decode(&data) {
+                Some(payload) => payload,
+                None => continue,
+            };
 
             // Handle the payload depending on the opcode
             match payload.op_code {
                 PayloadOpcode::Dispatch => {
-                    // Update the sequence number
+                    let event_name = payload.event_name.unwrap();
+                    // The session is established, so the next time the connection is lost it starts with a short wait again
+                    if event_name == "READY" || event_name == "RESUMED" {
+                        *backoff = MIN_RECONNECT_BACKOFF;
+                    }
+                    // Update the sequence number, and remember the session so that it can be resumed
                     {
-                        *sequence_num.lock().unwrap() = Some(payload.sequence_num.unwrap() as u64);
+                        let mut session = session.lock().unwrap();
+                        session.sequence_num = Some(payload.sequence_num.unwrap() as u64);
+                        if event_name == "READY" {
+                            session.session_id =
+                                payload.data["session_id"].as_str().map(String::from);
+                            session.resume_gateway_url = payload.data["resume_gateway_url"]
+                                .as_str()
+                                .map(String::from);
+                        }
+                    }
+                    // Member chunks are collected here so that requests for them don't wait on the event handlers
+                    if event_name == "GUILD_MEMBERS_CHUNK" {
+                        ctx.gateway.receive_member_chunk(&payload.data);
+                    }
+                    // Resumed has no data, so it is only passed on as a lifecycle event
+                    if event_name == "RESUMED" {
+                        WebsocketEventHandler::emit(
+                            events,
+                            shard_id,
+                            "SHARD_RESUMED",
+                            ShardResumed { shard_id },
+                        );
+                        continue;
+                    }
+                    let session_id = match event_name.as_str() {
+                        "READY" => payload.data["session_id"].as_str().map(String::from),
+                        _ => None,
+                    };
+                    events.send((shard_id, event_name, payload.data)).unwrap();
+                    if let Some(session_id) = session_id {
+                        WebsocketEventHandler::emit(
+                            events,
+                            shard_id,
+                            "SHARD_READY",
+                            ShardReady {
+                                shard_id,
+                                session_id,
+                            },
+                        );
                     }
-                    let event_name = payload.event_name.unwrap();
-                    events.send((event_name.to_string(), payload.data)).unwrap();
                 }
                 PayloadOpcode::Heartbeat => {
                     // Send a heartbeat if it is requested
-                    let seq = *sequence_num.lock().unwrap();
-                    let heartbeat =
-                        Message::Text(serde_json::to_string(&PayloadBase::new(seq)).unwrap());
-                    socket_send.send(heartbeat).unwrap();
+                    heartbeater.beat();
+                }
+                PayloadOpcode::Reconnect => return Disconnect::Reconnect,
+                PayloadOpcode::InvalidSession => {
+                    return Disconnect::InvalidSession(payload.data.as_bool().unwrap_or(false))
                 }
-                PayloadOpcode::Reconnect => {}
-                PayloadOpcode::InvalidSession => {}
                 PayloadOpcode::Hello => {
                     // This shouldn't happen so it is weird that we are in this branch
                 }
                 PayloadOpcode::HeartbeatAck => {
-                    // Acknowledged heartbeat
-                    // println!("Heartbeat acknowledged");
+                    // Acknowledged heartbeat. The time it took is the latency of the connection
+                    if let Some(latency) = heartbeater.ack() {
+                        ctx.gateway.set_latency(shard_id, latency);
+                    }
                 }
                 _ => {}
             }
             // interactions.send(interaction).await.unwrap();
         }
+        Disconnect::ConnectionLost(None)
+    }
+
+    /// Sends a lifecycle event to the bot, the same way that events from discord are sent
+    fn emit<T: Serialize>(
+        events: &Sender<(u64, String, Value)>,
+        shard_id: u64,
+        event_name: &str,
+        data: T,
+    ) {
+        if let Ok(data) = serde_json::to_value(data) {
+            events.send((shard_id, event_name.to_string(), data)).ok();
+        }
     }
 }
 
@@ -233,7 +798,7 @@ impl SocketClient for WebsocketEventHandler {
     }
 
     // Get the command channel associated with the socket client
-    fn get_command_channel(&self) -> Receiver<(String, Value)> {
+    fn get_command_channel(&self) -> Receiver<(u64, String, Value)> {
         self.event_receiver.clone()
     }
 }
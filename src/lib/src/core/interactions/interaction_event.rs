@@ -1,18 +1,22 @@
 use discrab_codegen::CommandArg;
-use hyper::{Body, Method, Request};
 
 use crate::{
     api::{guild::guild_member::GuildMember, user::User, Message, Snowflake, ApplicationCommandOptionValue},
     core::{
         abstraction::traits::CommandArg,
-        http::rate_limit_client::{send_request_noparse, RequestRoute},
+        http::{
+            multipart::{add_attachments, message_body},
+            rate_limit_client::{send_request, send_request_noparse},
+            request_queue::RequestPriority,
+            route::Route,
+        },
     },
     util::error::Error,
-    Context, BASE_URL,
+    Context,
 };
 
 use super::typing::{
-    Interaction, InteractionCallbackData, InteractionCallbackType, InteractionData,
+    Interaction, InteractionCallbackData, InteractionCallbackType, InteractionData, MessageData,
     InteractionResponse, InteractionType, InteractionDataOption,
 };
 
@@ -107,10 +111,10 @@ impl InteractionCtx {
     /// Responds to an interaction with a loading state.
     pub async fn respond_loading(&self) -> Result<(), Error> {
         self.respond(
-            format!(
-                "{}/interactions/{}/{}/callback",
-                BASE_URL, self.id, self.token
-            ),
+            Route::CreateInteractionResponse {
+                interaction_id: self.id,
+                interaction_token: self.token.clone(),
+            },
             InteractionResponse {
                 type_: InteractionCallbackType::DeferredChannelMessageWithSource,
                 data: None,
@@ -122,10 +126,10 @@ impl InteractionCtx {
     // Responds to an interaction with a message
     pub async fn respond_message(&self, msg: InteractionCallbackData) -> Result<(), Error> {
         self.respond(
-            format!(
-                "{}/interactions/{}/{}/callback",
-                BASE_URL, self.id, self.token
-            ),
+            Route::CreateInteractionResponse {
+                interaction_id: self.id,
+                interaction_token: self.token.clone(),
+            },
             InteractionResponse {
                 type_: InteractionCallbackType::ChannelMessageWithSource,
                 data: Some(msg),
@@ -134,29 +138,78 @@ impl InteractionCtx {
         .await
     }
 
-    // Update the response that was sent with a new response
-    pub async fn update_response(&self, response: InteractionResponse) -> Result<(), Error> {
-        self.respond(
-            format!("{}/interactions/{}/{}/m", BASE_URL, self.id, self.token),
-            response,
+    /**
+     * Edits the message that was sent as the response to the interaction, or the loading state from `respond_loading`.
+     * Files in the message are uploaded with it
+     * @param msg The new message
+     * @docs <https://discord.com/developers/docs/interactions/receiving-and-responding#edit-original-interaction-response>
+     */
+    pub async fn update_response(&self, msg: MessageData) -> Result<Message, Error> {
+        self.send_message(
+            Route::EditOriginalInteractionResponse {
+                webhook_id: self.application_id,
+                webhook_token: self.token.clone(),
+            },
+            msg,
+        )
+        .await
+    }
+
+    /**
+     * Sends a followup message for the interaction. It can be sent after the interaction has been responded to, for up to 15 minutes.
+     * Files in the message are uploaded with it
+     * @param msg The message to send
+     * @docs <https://discord.com/developers/docs/interactions/receiving-and-responding#create-followup-message>
+     */
+    pub async fn followup(&self, msg: MessageData) -> Result<Message, Error> {
+        self.send_message(
+            Route::CreateFollowupMessage {
+                webhook_id: self.application_id,
+                webhook_token: self.token.clone(),
+            },
+            msg,
         )
         .await
     }
 
-    // TODO I think I have to move this into the individual thread because the requests have to be different for each one
-    async fn respond(&self, uri: String, payload: InteractionResponse) -> Result<(), Error> {
-        let route = RequestRoute {
-            base_route: "interactions/<interaction_id>/<interaction_token>".to_string(),
-            major_param: "".to_string(),
+    /// Sends a message to one of the interaction's webhook endpoints, and returns the message
+    async fn send_message(&self, route: Route, msg: MessageData) -> Result<Message, Error> {
+        let mut payload = serde_json::to_value(&msg).unwrap();
+        add_attachments(&mut payload, &msg.files);
+        let (content_type, body) = message_body(&payload, &msg.files);
+
+        let request_builder = route
+            .request(&self.__ctx__)
+            .header("content-type", content_type)
+            .body(body)
+            .unwrap();
+
+        send_request(self.__ctx__.clone(), route.bucket(), request_builder).await
+    }
+
+    async fn respond(&self, route: Route, payload: InteractionResponse) -> Result<(), Error> {
+        let files = match &payload.data {
+            Some(InteractionCallbackData::Message(message)) => message.files.clone(),
+            _ => Vec::new(),
         };
-        let request_builder = Request::builder()
-            .method(Method::POST)
-            .uri(uri)
-            .header("content-type", "application/json")
-            .body(Body::from(serde_json::to_string(&payload).unwrap()))
+        let mut payload = serde_json::to_value(&payload).unwrap();
+        if let Some(data) = payload.get_mut("data") {
+            add_attachments(data, &files);
+        }
+        let (content_type, body) = message_body(&payload, &files);
+
+        let request_builder = route
+            .request(&self.__ctx__)
+            .header("content-type", content_type)
+            .body(body)
             .unwrap();
 
-        let req = send_request_noparse(self.__ctx__.clone(), route, request_builder)
+        // Interactions have to be responded to quickly, so the callback is sent before other requests unless a priority was set
+        let ctx = match self.__ctx__.settings.priority {
+            Some(_) => self.__ctx__.clone(),
+            None => self.__ctx__.with_priority(RequestPriority::Interaction),
+        };
+        let req = send_request_noparse(ctx, route.bucket(), request_builder)
             .await;
         if let Err(e) = &req {
             println!("{:?}", e);
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
 use crate::{
-    api::{channel::{attachment::Attachment, embed::Embed}, ApplicationCommandOptionValue},
+    api::{channel::{attachment::{Attachment, AttachmentFile}, embed::Embed}, ApplicationCommandOptionValue},
     api::ApplicationCommandType,
     core::abstraction::traits::CommandArg,
     discord::{
@@ -134,6 +134,7 @@ impl InteractionCallbackData {
             flags: None,
             components: None,
             attachments: None,
+            files: Vec::new(),
         })
     }
 }
@@ -169,6 +170,9 @@ pub struct MessageData {
     pub components: Option<Vec<MessageComponent>>,
     /// attachment objects with filename and description
     pub attachments: Option<Vec<Attachment>>,
+    /// files to upload with the message
+    #[serde(skip)]
+    pub files: Vec<AttachmentFile>,
 }
 
 bitflags! {
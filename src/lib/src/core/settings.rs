@@ -1,10 +1,118 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{discord::gateway::presence::UpdatePresence, BASE_URL};
+
+use super::http::{request_queue::RequestPriority, retry_policy::RetryPolicy};
+use super::interactions::handler::{
+    encoding::GatewayEncoding,
+    session_store::SessionStore,
+    events::{IdentifyProperties, Intents},
+    shard_manager::ShardConfig,
+};
+
 #[derive(Clone, Default)]
 pub struct Settings {
     pub debug: bool,
+    /// Which shards the bot runs. By default, the number of shards that discord recommends is used
+    pub shards: ShardConfig,
+    /// The gateway intents that the bot identifies with. If none are set, they are worked out from the registered event handlers
+    pub intents: Option<Intents>,
+    /// Total number of members (between 50 and 250) where the gateway will stop sending offline members in the guild member list
+    pub large_threshold: Option<u64>,
+    /// The presence that the bot has when it connects
+    pub presence: Option<UpdatePresence>,
+    /// The connection properties that are sent when the bot identifies
+    pub identify_properties: IdentifyProperties,
+    /// Whether the gateway connection uses `zlib-stream` transport compression
+    pub compression: bool,
+    /// The format that payloads are sent in over the gateway
+    pub encoding: GatewayEncoding,
+    /// How long the bot waits for gateway connections to close and queued requests to be sent when it shuts down. Defaults to 10 seconds
+    pub shutdown_timeout: Option<Duration>,
+    /// Where gateway sessions are saved on shutdown, so that they can be resumed when the bot starts again
+    pub session_store: Option<Arc<dyn SessionStore>>,
+    /// How long a request waits for a response, including the time it spends waiting for rate limits, before it is cancelled. By default, requests wait until they get a response
+    pub request_timeout: Option<Duration>,
+    /// Overrides the http client's retry policy. This is set for single requests with `ctx.with_retry_policy`
+    pub retry_policy: Option<RetryPolicy>,
+    /// The url that requests to the api are sent to. By default, requests are sent to discord
+    pub base_url: Option<String>,
+    /// How urgent requests are, for queues that sort by priority. This is set for single requests with `ctx.with_priority`
+    pub priority: Option<RequestPriority>,
+    /// The reason that is shown in the guild's audit log for the actions that requests make. This is set for single requests with `ctx.with_reason`
+    pub audit_log_reason: Option<String>,
 }
 
 impl Settings {
     pub fn set_debug(&mut self, debug: bool) {
         self.debug = debug;
     }
+
+    /// Set which shards the bot runs
+    pub fn set_shards(&mut self, shards: ShardConfig) {
+        self.shards = shards;
+    }
+
+    /// Set the gateway intents that the bot identifies with
+    pub fn set_intents(&mut self, intents: Intents) {
+        self.intents = Some(intents);
+    }
+
+    /// Set the total number of members (between 50 and 250) where the gateway will stop sending offline members in the guild member list
+    pub fn set_large_threshold(&mut self, large_threshold: u64) {
+        self.large_threshold = Some(large_threshold);
+    }
+
+    /// Set the presence that the bot has when it connects
+    pub fn set_presence(&mut self, presence: UpdatePresence) {
+        self.presence = Some(presence);
+    }
+
+    /// Set the connection properties that are sent when the bot identifies
+    pub fn set_identify_properties(&mut self, properties: IdentifyProperties) {
+        self.identify_properties = properties;
+    }
+
+    /// Set whether the gateway connection uses `zlib-stream` transport compression.
+    /// This lowers bandwidth, especially for large bots, at the cost of some CPU time
+    pub fn set_compression(&mut self, compression: bool) {
+        self.compression = compression;
+    }
+
+    /// Set the format that payloads are sent in over the gateway.
+    /// ETF is faster to parse than json, and is available with the `etf` feature
+    pub fn set_encoding(&mut self, encoding: GatewayEncoding) {
+        self.encoding = encoding;
+    }
+
+    /// Set how long the bot waits for gateway connections to close and queued requests to be sent when it shuts down
+    pub fn set_shutdown_timeout(&mut self, timeout: Duration) {
+        self.shutdown_timeout = Some(timeout);
+    }
+
+    /// Set where gateway sessions are saved on shutdown. When the bot starts again, it will try to resume the saved sessions before identifying
+    pub fn set_session_store(&mut self, store: impl SessionStore + 'static) {
+        self.session_store = Some(Arc::new(store));
+    }
+
+    /// Set how long requests wait for a response before they are cancelled.
+    /// It can be changed for a single request with `ctx.with_timeout`
+    pub fn set_request_timeout(&mut self, timeout: Duration) {
+        self.request_timeout = Some(timeout);
+    }
+
+    /// Set the url that requests to the api are sent to, such as a local REST proxy. It shouldn't end with a `/`
+    pub fn set_base_url(&mut self, base_url: String) {
+        self.base_url = Some(base_url);
+    }
+
+    /// Set how urgent requests are. Interaction callbacks are always sent with `Interaction` priority unless this is set
+    pub fn set_priority(&mut self, priority: RequestPriority) {
+        self.priority = Some(priority);
+    }
+
+    /// Get the url that requests to the api are sent to
+    pub fn get_base_url(&self) -> &str {
+        self.base_url.as_deref().unwrap_or(BASE_URL)
+    }
 }
@@ -43,6 +43,29 @@ pub struct Activity {
     pub buttons: Option<Vec<ActivityButton>>,
 }
 
+impl Activity {
+    /// Creates an activity that can be sent as part of the bot's presence. Bots can only set the name, type, and url of an activity
+    pub fn new(name: String, type_: ActivityType) -> Self {
+        Self {
+            name,
+            type_,
+            url: None,
+            created_at: chrono::Utc::now().timestamp_millis(),
+            timestamps: None,
+            application_id: None,
+            details: None,
+            state: None,
+            emoji: None,
+            party: None,
+            assets: None,
+            secrets: None,
+            instance: None,
+            flags: None,
+            buttons: None,
+        }
+    }
+}
+
 /**
  * Activity Type
  * @docs <https://discord.com/developers/docs/topics/gateway#activity-object-activity-types>
@@ -2,7 +2,10 @@ use discrab_codegen::CommandArg;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    core::abstraction::traits::CommandArg,
+    core::{
+        abstraction::traits::CommandArg,
+        interactions::handler::{events::PayloadData, gateway_payload::PayloadOpcode},
+    },
     discord::{resources::user::User, snowflake::Snowflake},
 };
 
@@ -39,3 +42,56 @@ pub struct ClientStatus {
     /// the user's status set for an active web (browser, bot account) application session
     pub web: Option<String>,
 }
+
+/**
+ * Gateway Presence Update Structure
+ * Sent by the client to indicate a presence or status update.
+ * @docs <https://discord.com/developers/docs/topics/gateway#update-presence-gateway-presence-update-structure>
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct UpdatePresence {
+    /// unix time (in milliseconds) of when the client went idle, or null if the client is not idle
+    pub since: Option<u64>,
+    /// the user's activities
+    pub activities: Vec<Activity>,
+    /// the user's new status
+    pub status: StatusType,
+    /// whether or not the client is afk
+    pub afk: bool,
+}
+
+impl UpdatePresence {
+    pub fn new(status: StatusType, activities: Vec<Activity>, afk: bool) -> Self {
+        Self {
+            since: None,
+            activities,
+            status,
+            afk,
+        }
+    }
+}
+
+impl PayloadData for UpdatePresence {
+    fn get_opcode(&self) -> PayloadOpcode {
+        PayloadOpcode::PresenceUpdate
+    }
+}
+
+/**
+ * Status Types
+ * @docs <https://discord.com/developers/docs/topics/gateway#update-presence-status-types>
+ */
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusType {
+    /// Online
+    Online,
+    /// Do Not Disturb
+    Dnd,
+    /// AFK
+    Idle,
+    /// Invisible and shown as offline
+    Invisible,
+    /// Offline
+    Offline,
+}
@@ -2,11 +2,11 @@ use std::{sync::Arc, hash::{Hash, Hasher}};
 
 use crate::{
     api::{application::Application, channel::typing::ChannelType, Snowflake},
-    core::{http::rate_limit_client::{send_request, RequestRoute, send_request_noparse}},
+    core::{http::{rate_limit_client::{send_request, send_request_noparse}, route::Route}},
     util::error::Error,
-    Context, BASE_URL, SubRegisterable,
+    Context, SubRegisterable,
 };
-use hyper::{Body, Method, Request};
+use hyper::Body;
 use serde::{self, Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
@@ -273,39 +273,33 @@ impl ApplicationCommand {
     pub async fn get_global(ctx: Context, id: Snowflake) -> Result<ApplicationCommand, Error> {
         let slf = Application::get_self(ctx.clone()).await?;
 
-        let route = RequestRoute {
-            base_route: "/applications/{application.id}/commands/{command.id}/".to_string(),
-            major_param: "".to_string(),
+        let route = Route::GetGlobalCommand {
+            application_id: slf.id,
+            command_id: id,
         };
-        let request_builder = Request::builder()
-            .method(Method::GET)
-            .uri(format!(
-                "{}/applications/{}/commands/{}/",
-                BASE_URL, slf.id, id
-            ))
+        let request_builder = route
+            .request(&ctx)
             .header("content-type", "application/json")
             .body(Body::empty())
             .unwrap();
 
-        send_request(ctx, route, request_builder).await
+        send_request(ctx, route.bucket(), request_builder).await
     }
 
     /// Lists the global application commands associated with the application
     pub async fn list_global(ctx: Context) -> Result<Vec<ApplicationCommand>, Error> {
         let slf = Application::get_self(ctx.clone()).await?;
 
-        let route = RequestRoute {
-            base_route: "/applications/{application.id}/commands".to_string(),
-            major_param: "".to_string(),
+        let route = Route::GetGlobalCommands {
+            application_id: slf.id,
         };
-        let request_builder = Request::builder()
-            .method(Method::GET)
-            .uri(format!("{}/applications/{}/commands", BASE_URL, slf.id))
+        let request_builder = route
+            .request(&ctx)
             .header("content-type", "application/json")
             .body(Body::empty())
             .unwrap();
 
-        send_request(ctx, route, request_builder).await
+        send_request(ctx, route.bucket(), request_builder).await
     }
 
     /**
@@ -321,18 +315,16 @@ impl ApplicationCommand {
     ) -> Result<ApplicationCommand, Error> {
         let slf = Application::get_self(ctx.clone()).await?;
 
-        let route = RequestRoute {
-            base_route: "/applications/{application.id}/commands".to_string(),
-            major_param: "".to_string(),
+        let route = Route::CreateGlobalCommand {
+            application_id: slf.id,
         };
-        let request_builder = Request::builder()
-            .method(Method::POST)
-            .uri(format!("{}/applications/{}/commands", BASE_URL, slf.id))
+        let request_builder = route
+            .request(&ctx)
             .header("content-type", "application/json")
             .body(Body::from(serde_json::to_string(&payload).unwrap()))
             .unwrap();
 
-        send_request(ctx, route, request_builder).await
+        send_request(ctx, route.bucket(), request_builder).await
     }
 
     /**
@@ -351,17 +343,16 @@ impl ApplicationCommand {
     ) -> Result<(), Error> {
         let slf = Application::get_self(ctx.clone()).await?;
 
-        let route = RequestRoute {
-            base_route: "/applications/{application.id}/commands/{}".to_string(),
-            major_param: "".to_string(),
+        let route = Route::EditGlobalCommand {
+            application_id: slf.id,
+            command_id: id,
         };
-        let request_builder = Request::builder()
-            .method(Method::PATCH)
-            .uri(format!("{}/applications/{}/commands/{}", BASE_URL, slf.id, id))
+        let request_builder = route
+            .request(&ctx)
             .header("content-type", "application/json")
             .body(Body::from(serde_json::to_string(&payload).unwrap()))
             .unwrap();
 
-        send_request_noparse(ctx, route, request_builder).await
+        send_request_noparse(ctx, route.bucket(), request_builder).await
     }
 }
@@ -1,12 +1,12 @@
 use crate::{
-    core::http::rate_limit_client::{send_request, RequestRoute},
+    core::http::{rate_limit_client::send_request, route::Route},
     discord::{snowflake::Snowflake, teams::Team},
     util::error::Error,
-    Context, BASE_URL,
+    Context,
 };
 
 use bitflags::bitflags;
-use hyper::{Body, Method, Request};
+use hyper::Body;
 use serde::{Deserialize, Deserializer, Serialize};
 
 use super::user::User;
@@ -86,17 +86,13 @@ impl<'de> Deserialize<'de> for ApplicationFlags {
 impl Application {
     /// Gets the application associated with the bot
     pub async fn get_self(ctx: Context) -> Result<Application, Error> {
-        let route = RequestRoute {
-            base_route: "/oauth2/applications".to_string(),
-            major_param: "".to_string(),
-        };
-        let request_builder = Request::builder()
-            .method(Method::GET)
-            .uri(format!("{}/oauth2/applications/@me", BASE_URL))
+        let route = Route::GetCurrentApplication {};
+        let request_builder = route
+            .request(&ctx)
             .header("content-type", "application/json")
             .body(Body::empty())
             .unwrap();
 
-        send_request(ctx, route, request_builder).await
+        send_request(ctx, route.bucket(), request_builder).await
     }
 }
@@ -1,3 +1,7 @@
+use std::path::Path;
+
+use async_std::io::{Read, ReadExt};
+use hyper::body::Bytes;
 use serde::{Deserialize, Serialize};
 
 use crate::discord::snowflake::Snowflake;
@@ -28,3 +32,60 @@ pub struct Attachment {
     /// whether this attachment is ephemeral
     pub ephemeral: Option<bool>,
 }
+
+/// A file that is uploaded with a message. Add it to a message with `MessageBuilder::add_file`, or to the `files` of a `MessageData`
+#[derive(Clone, Debug)]
+pub struct AttachmentFile {
+    /// The name of the file, which discord uses to work out its type. Like `image.png`
+    pub filename: String,
+    /// The description (alt text) of the file
+    pub description: Option<String>,
+    /// The contents of the file
+    pub data: Bytes,
+}
+
+impl AttachmentFile {
+    /**
+     * Creates a file from its contents
+     * @param filename The name of the file, like `log.txt`
+     * @param data The contents of the file
+     */
+    pub fn from_bytes(filename: &str, data: impl Into<Bytes>) -> Self {
+        AttachmentFile {
+            filename: filename.to_string(),
+            description: None,
+            data: data.into(),
+        }
+    }
+
+    /**
+     * Reads a file from the disk. The file is named after the last part of the path
+     * @param path The path of the file to read
+     */
+    pub async fn from_path(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref();
+        let data = async_std::fs::read(path).await?;
+        let filename = path
+            .file_name()
+            .map_or_else(|| "file".to_string(), |name| name.to_string_lossy().into_owned());
+        Ok(AttachmentFile::from_bytes(&filename, data))
+    }
+
+    /**
+     * Reads a file from a reader until it ends
+     * @param filename The name of the file, like `log.txt`
+     * @param reader The reader to read the contents of the file from
+     */
+    pub async fn from_reader(filename: &str, mut reader: impl Read + Unpin) -> std::io::Result<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).await?;
+        Ok(AttachmentFile::from_bytes(filename, data))
+    }
+
+    /// Set the description (alt text) of the file
+    #[must_use]
+    pub fn set_description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+}
@@ -1,16 +1,19 @@
 use discrab_codegen::CommandArg;
-use hyper::{Body, Method, Request};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     api::Message,
     core::{
         abstraction::traits::CommandArg,
-        http::rate_limit_client::{send_request, RequestRoute},
+        http::{
+            multipart::{add_attachments, message_body},
+            rate_limit_client::send_request,
+            route::Route,
+        },
     },
     discord::{resources::user::User, snowflake::Snowflake},
     util::error::Error,
-    Context, BASE_URL,
+    Context,
 };
 
 use super::{
@@ -82,7 +85,7 @@ pub struct Channel {
 
 
 impl Channel {
-    /// Sends a message to a given channel.
+    /// Sends a message to a given channel. Files that were added to the message are uploaded with it.
     /// @param channel_id The id of the channel to send the message to.
     /// @param content The content of the message.
     /// @docs <https://discord.com/developers/docs/resources/channel#create-message>
@@ -91,24 +94,18 @@ impl Channel {
         channel_id: String,
         message: MessageBuilder,
     ) -> Result<Message, Error> {
-        let route = RequestRoute {
-            base_route: format!("/channels/{}/messages", channel_id.clone()),
-            major_param: channel_id.clone(),
-        };
+        let route = Route::CreateMessage { channel_id };
 
-        let body = Body::from(serde_json::to_string(&message).unwrap());
+        let mut payload = serde_json::to_value(&message).unwrap();
+        add_attachments(&mut payload, message.get_files());
+        let (content_type, body) = message_body(&payload, message.get_files());
 
-        let request_builder = Request::builder()
-            .method(Method::POST)
-            .uri(format!(
-                "{}/channels/{}/messages",
-                BASE_URL,
-                channel_id.clone()
-            ))
-            .header("content-type", "application/json")
+        let request_builder = route
+            .request(&ctx)
+            .header("content-type", content_type)
             .body(body)
             .unwrap();
 
-        send_request(ctx, route, request_builder).await
+        send_request(ctx, route.bucket(), request_builder).await
     }
 }
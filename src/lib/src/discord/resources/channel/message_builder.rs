@@ -6,6 +6,7 @@ use crate::{
 };
 
 use super::{
+    attachment::AttachmentFile,
     embed::{Embed, EmbedBuilder},
     typing::MessageReference,
 };
@@ -29,6 +30,9 @@ pub struct MessageBuilder {
     components: Option<Vec<MessageComponent>>,
     /// IDs of up to 3 stickers in the server to send in the message
     sticker_ids: Option<Vec<Snowflake>>,
+    /// the files to upload with the message
+    #[serde(skip)]
+    files: Vec<AttachmentFile>,
 }
 
 impl MessageBuilder {
@@ -41,6 +45,7 @@ impl MessageBuilder {
             message_reference: None,
             components: None,
             sticker_ids: None,
+            files: Vec::new(),
         }
     }
 
@@ -77,6 +82,18 @@ impl MessageBuilder {
         }
         self
     }
+
+    /// Upload a file with the message
+    #[must_use]
+    pub fn add_file(mut self, file: AttachmentFile) -> Self {
+        self.files.push(file);
+        self
+    }
+
+    /// Gets the files that are uploaded with the message
+    pub fn get_files(&self) -> &[AttachmentFile] {
+        &self.files
+    }
 }
 
 impl Default for MessageBuilder {
@@ -126,5 +126,7 @@ pub struct Guild {
 #[derive(Serialize, Deserialize, Clone, CommandArg)]
 pub struct UnavailableGuild {
     pub id: Snowflake,
+    /// Not sent when the user leaves or is removed from a guild
+    #[serde(default)]
     pub unavailable: bool,
 }
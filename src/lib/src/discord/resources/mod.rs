@@ -6,3 +6,4 @@ pub mod guild_scheduled_event;
 pub mod sticker;
 pub mod user;
 pub mod voice;
+pub mod webhook;
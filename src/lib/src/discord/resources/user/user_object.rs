@@ -1,15 +1,14 @@
 use discrab_codegen::CommandArg;
-use hyper::{Body, Method, Request};
+use hyper::Body;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     core::{
         abstraction::{traits::CommandArg, context::Context},
-        http::rate_limit_client::{send_request, RequestRoute},
+        http::{rate_limit_client::send_request, route::Route},
     },
     discord::{image_formats, snowflake::Snowflake},
     util::error::Error,
-    BASE_URL,
 };
 
 use super::UserFlags;
@@ -181,18 +180,14 @@ impl User {
     /// Gets the user with a given id
     /// @param id The id of the user
     pub async fn get(ctx: Context, id: String) -> Result<User, Error> {
-        let route = RequestRoute {
-            base_route: "/users".to_string(),
-            major_param: "".to_string(),
-        };
-        let request_builder = Request::builder()
-            .method(Method::GET)
-            .uri(format!("{}/users/{}", BASE_URL, id))
+        let route = Route::GetUser { user_id: id };
+        let request_builder = route
+            .request(&ctx)
             .header("content-type", "application/json")
             .body(Body::empty())
             .unwrap();
 
-        send_request::<User>(ctx, route, request_builder).await
+        send_request::<User>(ctx, route.bucket(), request_builder).await
     }
 
     /// Gets the bot's user object
@@ -0,0 +1,98 @@
+use hyper::Request;
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use crate::{
+    core::http::{
+        multipart::{add_attachments, message_body},
+        rate_limit_client::send_request,
+        route::Route,
+    },
+    discord::snowflake::Snowflake,
+    util::error::Error,
+    Context,
+};
+
+use super::{
+    channel::message::{Message, MessageBuilder},
+    user::User,
+};
+
+/**
+ * Webhook Object
+ * @docs <https://discord.com/developers/docs/resources/webhook#webhook-object>
+ */
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Webhook {
+    /// the id of the webhook
+    pub id: Snowflake,
+    /// the type of the webhook
+    #[serde(rename = "type")]
+    pub type_: WebhookType,
+    /// the guild id this webhook is for, if any
+    pub guild_id: Option<Snowflake>,
+    /// the channel id this webhook is for, if any
+    pub channel_id: Option<Snowflake>,
+    /// the user this webhook was created by (not returned when getting a webhook with its token)
+    pub user: Option<User>,
+    /// the default name of the webhook
+    pub name: Option<String>,
+    /// the default user avatar hash of the webhook
+    pub avatar: Option<String>,
+    /// the secure token of the webhook (returned for Incoming Webhooks)
+    pub token: Option<String>,
+    /// the bot/OAuth2 application that created this webhook
+    pub application_id: Option<Snowflake>,
+    /// the url used for executing the webhook (returned by the webhooks OAuth2 flow)
+    pub url: Option<String>,
+}
+
+/**
+ * Webhook Types
+ * @docs <https://discord.com/developers/docs/resources/webhook#webhook-object-webhook-types>
+ */
+#[derive(Serialize_repr, Deserialize_repr, Clone, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum WebhookType {
+    /// Incoming Webhooks can post messages to channels with a generated token
+    Incoming = 1,
+    /// Channel Follower Webhooks are internal webhooks used with Channel Following to post new messages into channels
+    ChannelFollower = 2,
+    /// Application webhooks are webhooks used with Interactions
+    Application = 3,
+}
+
+impl Webhook {
+    /**
+     * Sends a message through a webhook. Files that were added to the message are uploaded with it.
+     * @param webhook_id The id of the webhook
+     * @param token The token of the webhook
+     * @param message The message to send
+     * @docs <https://discord.com/developers/docs/resources/webhook#execute-webhook>
+     */
+    pub async fn execute(
+        ctx: Context,
+        webhook_id: Snowflake,
+        token: String,
+        message: MessageBuilder,
+    ) -> Result<Message, Error> {
+        let route = Route::ExecuteWebhook {
+            webhook_id,
+            webhook_token: token,
+        };
+
+        let mut payload = serde_json::to_value(&message).unwrap();
+        add_attachments(&mut payload, message.get_files());
+        let (content_type, body) = message_body(&payload, message.get_files());
+
+        // Waits for the message to be sent, so that it is returned
+        let request_builder = Request::builder()
+            .method(route.method())
+            .uri(format!("{}?wait=true", route.url(&ctx)))
+            .header("content-type", content_type)
+            .body(body)
+            .unwrap();
+
+        send_request(ctx, route.bucket(), request_builder).await
+    }
+}
@@ -29,11 +29,20 @@ impl<'de> Deserialize<'de> for Snowflake {
     where
         D: Deserializer<'de>,
     {
-        let s: String = Deserialize::deserialize(deserializer)?;
+        // Snowflakes are strings in json, but integers in ETF
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum RawSnowflake {
+            String(String),
+            Integer(u64),
+        }
 
-        match s.parse() {
-            Ok(sf) => Ok(Snowflake(sf)),
-            Err(_) => Err(de::Error::custom("invalid snowflake")),
+        match RawSnowflake::deserialize(deserializer)? {
+            RawSnowflake::String(s) => match s.parse() {
+                Ok(sf) => Ok(Snowflake(sf)),
+                Err(_) => Err(de::Error::custom("invalid snowflake")),
+            },
+            RawSnowflake::Integer(sf) => Ok(Snowflake(sf)),
         }
     }
 }
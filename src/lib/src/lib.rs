@@ -9,7 +9,9 @@ pub use crate::core::abstraction::{
     context::Context,
     event_dispatcher::{EventDispatcher, Events},
     interaction_router::InteractionRouter,
+    shutdown::ShutdownHandle,
 };
+pub use crate::util::error::{Error, FieldError, HttpError};
 
 pub mod macros {
     pub use discrab_codegen::*;
@@ -17,6 +19,7 @@ pub mod macros {
 
 pub mod builders {
     pub use crate::core::abstraction::option_builder::*;
+    pub use crate::api::channel::attachment::AttachmentFile;
     pub use crate::api::channel::message::MessageBuilder;
 }
 
@@ -56,11 +59,44 @@ pub mod events {
     pub use crate::api::voice::VoiceState;
     pub use crate::api::{Channel, Message};
     pub use crate::core::interactions::handler::events::dispatch_payloads::*;
+    pub use crate::core::interactions::handler::events::lifecycle_payloads::*;
     pub use crate::core::interactions::{
         interaction_event::InteractionCtx, typing::Interaction
     };
     pub use crate::discord::gateway::presence::PresenceUpdate;
 }
 
+/**
+ * Objects used to configure the bot's http client
+ */
+pub mod http {
+    pub use crate::core::http::http_handle::HttpHandle;
+    pub use crate::core::http::rate_limit_client::RLClient;
+    pub use crate::core::http::request_queue::{
+        BasicHttpQueue, HttpQueue, PriorityHttpQueue, RequestPriority,
+    };
+    pub use crate::core::http::retry_policy::RetryPolicy;
+    pub use crate::core::http::route::Route;
+    pub use crate::core::http::transport::{
+        HttpTransport, HyperTransport, MockTransport, RecordedRequest, TransportError,
+    };
+}
+
+/**
+ * Objects used to configure the bot's connection to the gateway
+ */
+pub mod gateway {
+    pub use crate::core::interactions::handler::encoding::GatewayEncoding;
+    pub use crate::core::interactions::handler::events::{IdentifyProperties, Intents};
+    pub use crate::core::interactions::handler::gateway_handle::{GuildMembers, MemberFilter};
+    pub use crate::core::interactions::handler::gateway_payload::GatewayCloseCode;
+    pub use crate::core::interactions::handler::session_store::{FileSessionStore, SessionStore};
+    pub use crate::core::interactions::handler::websocket::GatewaySession;
+    pub use crate::core::interactions::handler::shard_manager::ShardConfig;
+    pub use crate::discord::gateway::activity::{Activity, ActivityType};
+    pub use crate::discord::gateway::presence::{StatusType, UpdatePresence};
+}
+
+/// The url that requests are sent to, unless another one is set in the settings
 #[doc(hidden)]
 pub(crate) static BASE_URL: &str = "https://discord.com/api/v9";
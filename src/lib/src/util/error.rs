@@ -1,25 +1,275 @@
-use std::fmt::Display;
+use std::{fmt::Display, time::Duration};
 
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::core::http::transport::TransportError;
+
+/// An error from a request to discord, or from the gateway
 #[derive(Debug)]
-pub struct Error {
+pub enum Error {
+    /// Discord responded to a request with an error status
+    Http(HttpError),
+    /// The request couldn't be sent, or the connection failed before a response was received
+    Request {
+        /// The method and path of the request
+        route: String,
+        source: TransportError,
+    },
+    /// The request didn't get a response before its timeout
+    Timeout {
+        /// The method and path of the request
+        route: String,
+        timeout: Duration,
+    },
+    /// The response couldn't be parsed into the expected type
+    Parse {
+        /// The method and path of the request
+        route: String,
+        source: serde_json::Error,
+    },
+    /// The gateway failed in a way that can't be recovered from
+    Gateway(String),
+}
+
+/**
+ * An error response from discord
+ * @docs <https://discord.com/developers/docs/reference#error-messages>
+ */
+#[derive(Debug, Clone)]
+pub struct HttpError {
+    /// The method and path of the request
+    pub route: String,
+    /// The HTTP status code of the response
+    pub status: u16,
+    /// The JSON error code. None if the response doesn't have one, like for errors from a proxy
+    /// @docs <https://discord.com/developers/docs/topics/opcodes-and-status-codes#json-json-error-codes>
+    pub code: Option<u64>,
+    /// The message that describes the error
+    pub message: String,
+    /// The errors for each field of the request body, like for an "Invalid Form Body" error
+    pub errors: Vec<FieldError>,
+}
+
+/// An error with a single field of a request body
+#[derive(Debug, Clone)]
+pub struct FieldError {
+    /// The path to the field, like `embeds[0].fields[1].name`
+    pub path: String,
+    /// The error code, like `BASE_TYPE_REQUIRED`
+    pub code: String,
+    /// The message that describes the error
     pub message: String,
-    pub code: ErrorTypes,
+}
+
+/// The body of an error response
+#[derive(Deserialize)]
+struct ErrorBody {
+    code: u64,
+    message: String,
+    #[serde(default)]
+    errors: Value,
+}
+
+/// An error in the `_errors` list of a field
+#[derive(Deserialize)]
+struct ErrorBodyField {
+    code: String,
+    message: String,
+}
+
+impl HttpError {
+    /**
+     * Creates an error from a response that doesn't have a success status
+     * @param route The method and path of the request
+     * @param status The HTTP status code of the response
+     * @param body The body of the response
+     */
+    pub(crate) fn from_response(route: String, status: u16, body: &[u8]) -> Self {
+        match serde_json::from_slice::<ErrorBody>(body) {
+            Ok(body) => {
+                let mut errors = Vec::new();
+                flatten_errors(&body.errors, String::new(), &mut errors);
+                HttpError {
+                    route,
+                    status,
+                    code: Some(body.code),
+                    message: body.message,
+                    errors,
+                }
+            }
+            Err(_) => HttpError {
+                route,
+                status,
+                code: None,
+                message: String::from_utf8_lossy(body).into_owned(),
+                errors: Vec::new(),
+            },
+        }
+    }
+}
+
+/// Adds the errors of every field in a nested `errors` object to the list, with the path to each field
+fn flatten_errors(errors: &Value, path: String, out: &mut Vec<FieldError>) {
+    let fields = match errors.as_object() {
+        Some(fields) => fields,
+        None => return,
+    };
+    for (key, value) in fields {
+        if key == "_errors" {
+            let field_errors: Vec<ErrorBodyField> =
+                serde_json::from_value(value.clone()).unwrap_or_default();
+            out.extend(field_errors.into_iter().map(|error| FieldError {
+                path: path.clone(),
+                code: error.code,
+                message: error.message,
+            }));
+        } else if key.parse::<u64>().is_ok() {
+            flatten_errors(value, format!("{}[{}]", path, key), out);
+        } else if path.is_empty() {
+            flatten_errors(value, key.clone(), out);
+        } else {
+            flatten_errors(value, format!("{}.{}", path, key), out);
+        }
+    }
 }
 
 impl Error {
-    pub fn new(message: String, code: ErrorTypes) -> Error {
-        Error { message, code }
+    /// The HTTP status code of the response, if discord responded with an error
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Http(error) => Some(error.status),
+            _ => None,
+        }
+    }
+
+    /// The JSON error code that discord responded with, like 50013 for Missing Permissions
+    /// @docs <https://discord.com/developers/docs/topics/opcodes-and-status-codes#json-json-error-codes>
+    pub fn code(&self) -> Option<u64> {
+        match self {
+            Error::Http(error) => error.code,
+            _ => None,
+        }
+    }
+
+    /// The method and path of the request that failed, if the error came from a request
+    pub fn route(&self) -> Option<&str> {
+        match self {
+            Error::Http(HttpError { route, .. })
+            | Error::Request { route, .. }
+            | Error::Timeout { route, .. }
+            | Error::Parse { route, .. } => Some(route),
+            Error::Gateway(_) => None,
+        }
     }
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Error ({:?}) - {}", self.code, self.message)
+        match self {
+            Error::Http(error) => {
+                write!(f, "{} failed with status {}", error.route, error.status)?;
+                if let Some(code) = error.code {
+                    write!(f, " (code {})", code)?;
+                }
+                write!(f, " - {}", error.message)?;
+                for field in &error.errors {
+                    write!(f, "\n  {}: {} ({})", field.path, field.message, field.code)?;
+                }
+                Ok(())
+            }
+            Error::Request { route, source } => write!(f, "{} couldn't be sent - {}", route, source),
+            Error::Timeout { route, timeout } => write!(f, "{} timed out after {:?}", route, timeout),
+            Error::Parse { route, source } => {
+                write!(f, "The response to {} couldn't be parsed - {}", route, source)
+            }
+            Error::Gateway(message) => write!(f, "Gateway error - {}", message),
+        }
     }
 }
 
-#[derive(Debug)]
-pub enum ErrorTypes {
-    PARSE,
-    REQUEST,
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Request { source, .. } => Some(source),
+            Error::Parse { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error as _, io};
+
+    use crate::core::http::transport::TransportError;
+
+    use super::{Error, HttpError};
+
+    #[test]
+    fn flattens_nested_field_errors() {
+        // The example from https://discord.com/developers/docs/reference#error-messages, with an embed added
+        let body = br#"{
+            "code": 50035,
+            "errors": {
+                "activities": {
+                    "0": {
+                        "platform": {
+                            "_errors": [{"code": "BASE_TYPE_CHOICES", "message": "Value must be one of ('desktop', 'android', 'ios')."}]
+                        },
+                        "type": {
+                            "_errors": [{"code": "BASE_TYPE_CHOICES", "message": "Value must be one of (0, 1, 2, 3, 4, 5)."}]
+                        }
+                    }
+                },
+                "embeds": {
+                    "0": {"fields": {"1": {"name": {"_errors": [{"code": "BASE_TYPE_REQUIRED", "message": "This field is required"}]}}}}
+                }
+            },
+            "message": "Invalid Form Body"
+        }"#;
+        let error = HttpError::from_response("PATCH /users/@me".to_string(), 400, body);
+
+        assert_eq!(error.status, 400);
+        assert_eq!(error.code, Some(50035));
+        assert_eq!(error.message, "Invalid Form Body");
+
+        let mut errors: Vec<(&str, &str)> = error
+            .errors
+            .iter()
+            .map(|field| (field.path.as_str(), field.code.as_str()))
+            .collect();
+        errors.sort_unstable();
+        assert_eq!(
+            errors,
+            vec![
+                ("activities[0].platform", "BASE_TYPE_CHOICES"),
+                ("activities[0].type", "BASE_TYPE_CHOICES"),
+                ("embeds[0].fields[1].name", "BASE_TYPE_REQUIRED"),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_bodies_that_are_not_json() {
+        let error = HttpError::from_response("GET /gateway/bot".to_string(), 502, b"Bad Gateway");
+
+        assert_eq!(error.code, None);
+        assert_eq!(error.message, "Bad Gateway");
+        assert!(error.errors.is_empty());
+    }
+
+    #[test]
+    fn request_errors_keep_their_source() {
+        let error = Error::Request {
+            route: "GET /users/@me".to_string(),
+            source: TransportError::connect(io::Error::new(io::ErrorKind::ConnectionRefused, "connection refused")),
+        };
+
+        let transport = error.source().unwrap();
+        assert!(transport.downcast_ref::<TransportError>().unwrap().is_connect());
+        let cause = transport.source().unwrap();
+        assert_eq!(cause.downcast_ref::<io::Error>().unwrap().kind(), io::ErrorKind::ConnectionRefused);
+        assert!(cause.source().is_none());
+    }
 }
@@ -17,3 +17,20 @@ pub fn print_debug(module: &str, message: String) {
         .unwrap();
     writeln!(&mut stdout, "{}", message).unwrap();
 }
+
+/**
+ * Prints a warning to console with the same style as `print_debug`
+ * The output will look like this: `[module] message`
+ */
+pub fn print_warning(module: &str, message: String) {
+    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+    stdout
+        .set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))
+        .unwrap();
+    write!(&mut stdout, "[{}]: ", module).unwrap();
+
+    stdout
+        .set_color(ColorSpec::new().set_fg(Some(Color::White)))
+        .unwrap();
+    writeln!(&mut stdout, "{}", message).unwrap();
+}
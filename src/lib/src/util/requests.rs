@@ -11,3 +11,20 @@ where
         .and_then(|header| header.to_str().ok())
         .and_then(|header_str| header_str.parse().ok())
 }
+
+/**
+ * Percent-encodes a string so that it can be sent in a header, like the `X-Audit-Log-Reason` header.
+ * Every byte except letters, digits and `-_.~` is encoded, so non-ASCII text is sent as encoded UTF-8
+ */
+pub fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}